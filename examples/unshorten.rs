@@ -1,5 +1,4 @@
 use std::{env, process::exit};
-use urlexpand;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,346 @@
+// Expansion of many urls at once, for applications
+use std::{
+    collections::HashSet,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    future::BoxFuture,
+    io::{AsyncBufRead, AsyncBufReadExt},
+    stream::{self, FuturesOrdered},
+    FutureExt, Stream, StreamExt,
+};
+
+use crate::{normalize_short_url, ExpandedUrl, Expander, Result};
+
+/// How a batch runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOptions {
+    /// How many urls are expanded at the same time (default: 16)
+    pub concurrency: usize,
+    /// Expand the urls that only differ by a missing scheme or the case of
+    /// their host once (default: on)
+    pub dedupe: bool,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 16,
+            dedupe: true,
+        }
+    }
+}
+
+/// The expansion of a url of a batch
+#[derive(Debug)]
+pub struct BatchItem {
+    /// The url, as read
+    pub url: String,
+    pub result: Result<ExpandedUrl>,
+}
+
+/// What became of the urls of a batch
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// The expansion of every url, in the order of the input; once per url
+    /// when deduplicating
+    pub items: Vec<BatchItem>,
+    /// Urls read, duplicates included; empty lines and `#` comments are not
+    pub read: usize,
+    /// Urls left out as duplicates of earlier ones
+    pub duplicates: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+impl BatchReport {
+    /// The expansions that failed
+    pub fn failures(&self) -> impl Iterator<Item = &BatchItem> {
+        self.items.iter().filter(|item| item.result.is_err())
+    }
+}
+
+/// What urls normalize to when deduplicating them
+fn dedupe_key(url: &str) -> String {
+    normalize_short_url(url).map_or_else(|| url.into(), String::from)
+}
+
+/// Expand the urls of a reader, one per line (empty lines and `#` comments
+/// are skipped), `options.concurrency` at a time, and report on them; the
+/// urls are read as they are expanded
+///
+/// ## Example
+/// ```rust,no_run
+/// use futures::io::{AllowStdIo, BufReader};
+/// use urlexpand::{unshorten_from_reader, BatchOptions, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let file = BufReader::new(AllowStdIo::new(std::fs::File::open("urls.txt")?));
+/// let expander = Expander::builder().build()?;
+/// let report = unshorten_from_reader(&expander, file, BatchOptions::default()).await?;
+/// println!("{} expanded, {} failed", report.succeeded, report.failed);
+/// for failure in report.failures() {
+///     println!("{}: {:?}", failure.url, failure.result);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn unshorten_from_reader<R: AsyncBufRead + Unpin>(
+    expander: &Expander,
+    reader: R,
+    options: BatchOptions,
+) -> Result<BatchReport> {
+    let lines = stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok(line), Some(reader))),
+            Err(e) => Some((Err(e.into()), None)),
+        }
+    });
+    expand_batch(expander, lines, options).await
+}
+
+/// Expand a batch of urls; the first error of `lines` fails it
+async fn expand_batch<S>(
+    expander: &Expander,
+    lines: S,
+    options: BatchOptions,
+) -> Result<BatchReport>
+where
+    S: Stream<Item = Result<String>>,
+{
+    let mut report = BatchReport::default();
+    let mut seen = HashSet::new();
+    let mut error = None;
+    let urls = lines.filter_map(|line: Result<String>| {
+        let url = match line {
+            Ok(line) => line.trim().to_string(),
+            Err(e) => {
+                error.get_or_insert(e);
+                return futures::future::ready(None);
+            }
+        };
+        if url.is_empty() || url.starts_with('#') {
+            return futures::future::ready(None);
+        }
+        report.read += 1;
+        if options.dedupe && !seen.insert(dedupe_key(&url)) {
+            report.duplicates += 1;
+            return futures::future::ready(None);
+        }
+        futures::future::ready(Some(url))
+    });
+    let items: Vec<BatchItem> = urls
+        .map(|url| async move {
+            let result = expander.expand(&url).await;
+            BatchItem { url, result }
+        })
+        .buffered(options.concurrency.max(1))
+        .collect()
+        .await;
+    if let Some(e) = error {
+        return Err(e);
+    }
+    report.failed = items.iter().filter(|item| item.result.is_err()).count();
+    report.succeeded = items.len() - report.failed;
+    report.items = items;
+    Ok(report)
+}
+
+/// Expand urls like [`unshorten_from_reader`], blocking until they are all
+/// expanded, with an expander of the default configuration (see
+/// [`set_default_config`](crate::set_default_config)) and a runtime of its
+/// own; for scripts and command line tools that are not async
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{unshorten_many_blocking, BatchOptions};
+///
+/// let urls = ["https://bit.ly/3alqLKi", "https://t.co/abc"];
+/// let report = unshorten_many_blocking(urls, BatchOptions::default())?;
+/// for item in &report.items {
+///     println!("{} -> {:?}", item.url, item.result.as_ref().map(|expanded| &expanded.url));
+/// }
+/// # Ok::<(), urlexpand::Error>(())
+/// ```
+#[cfg(feature = "blocking")]
+pub fn unshorten_many_blocking<I>(urls: I, options: BatchOptions) -> Result<BatchReport>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let expander = Expander::builder()
+        .config(&crate::config::default_config())
+        .build()?;
+    let urls = stream::iter(urls).map(|url| Ok(url.as_ref().to_string()));
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(expand_batch(&expander, urls, options))
+}
+
+/// The expansion of a url, owning what it needs
+fn expansion<U: AsRef<str>>(expander: &Expander, url: U) -> BoxFuture<'static, BatchItem> {
+    let expander = expander.clone();
+    let url = url.as_ref().to_string();
+    async move {
+        let result = expander.expand(&url).await;
+        BatchItem { url, result }
+    }
+    .boxed()
+}
+
+/// Marks the [`UrlExpandExt`] of iterators
+#[cfg(feature = "blocking")]
+pub struct Blocking;
+
+/// Marks the [`UrlExpandExt`] of streams
+pub struct Async;
+
+/// Expand the urls of an iterator (with the `blocking` feature) or of a
+/// stream, `concurrency` at a time, as an adaptor yielding a [`BatchItem`]
+/// for every url, in order
+///
+/// ## Example
+/// ```rust,no_run
+/// use futures::{stream, StreamExt};
+/// use urlexpand::{Expander, UrlExpandExt};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let expander = Expander::builder().build()?;
+/// let urls = vec!["https://bit.ly/3alqLKi", "https://t.co/abc"];
+/// let mut expansions = stream::iter(urls).expand_urls(&expander, 16);
+/// while let Some(item) = expansions.next().await {
+///     println!("{} -> {:?}", item.url, item.result.map(|expanded| expanded.url));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub trait UrlExpandExt<Kind> {
+    type Expansions;
+
+    fn expand_urls(self, expander: &Expander, concurrency: usize) -> Self::Expansions;
+}
+
+impl<S> UrlExpandExt<Async> for S
+where
+    S: Stream,
+    S::Item: AsRef<str>,
+{
+    type Expansions = ExpandUrls<S>;
+
+    fn expand_urls(self, expander: &Expander, concurrency: usize) -> ExpandUrls<S> {
+        ExpandUrls {
+            urls: Box::pin(self),
+            done: false,
+            expander: expander.clone(),
+            concurrency: concurrency.max(1),
+            pending: FuturesOrdered::new(),
+        }
+    }
+}
+
+/// The expansions of the urls of a stream, from [`UrlExpandExt::expand_urls`]
+pub struct ExpandUrls<S> {
+    urls: Pin<Box<S>>,
+    done: bool,
+    expander: Expander,
+    concurrency: usize,
+    pending: FuturesOrdered<BoxFuture<'static, BatchItem>>,
+}
+
+impl<S> Stream for ExpandUrls<S>
+where
+    S: Stream,
+    S::Item: AsRef<str>,
+{
+    type Item = BatchItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BatchItem>> {
+        let this = self.get_mut();
+        while !this.done && this.pending.len() < this.concurrency {
+            match this.urls.as_mut().poll_next(cx) {
+                Poll::Ready(Some(url)) => this.pending.push_back(expansion(&this.expander, url)),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        match this.pending.poll_next_unpin(cx) {
+            // the urls are still coming
+            Poll::Ready(None) if !this.done => Poll::Pending,
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<I> UrlExpandExt<Blocking> for I
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Expansions = ExpandUrlsBlocking<I>;
+
+    fn expand_urls(self, expander: &Expander, concurrency: usize) -> ExpandUrlsBlocking<I> {
+        ExpandUrlsBlocking {
+            urls: self,
+            expander: expander.clone(),
+            concurrency: concurrency.max(1),
+            pending: FuturesOrdered::new(),
+            runtime: None,
+        }
+    }
+}
+
+/// The expansions of the urls of an iterator, from
+/// [`UrlExpandExt::expand_urls`]; they run on a runtime of their own, so the
+/// iterator must not be used from async code. When that runtime cannot be
+/// started, every url is yielded with an `Error::StdIo`
+#[cfg(feature = "blocking")]
+pub struct ExpandUrlsBlocking<I> {
+    urls: I,
+    expander: Expander,
+    concurrency: usize,
+    pending: FuturesOrdered<BoxFuture<'static, BatchItem>>,
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "blocking")]
+impl<I> Iterator for ExpandUrlsBlocking<I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Item = BatchItem;
+
+    fn next(&mut self) -> Option<BatchItem> {
+        if self.runtime.is_none() {
+            match tokio::runtime::Runtime::new() {
+                Ok(runtime) => self.runtime = Some(runtime),
+                // the urls fail one by one, as their expansions would
+                Err(e) => {
+                    let url = self.urls.next()?;
+                    return Some(BatchItem {
+                        url: url.as_ref().to_string(),
+                        result: Err(e.into()),
+                    });
+                }
+            }
+        }
+        while self.pending.len() < self.concurrency {
+            let Some(url) = self.urls.next() else {
+                break;
+            };
+            self.pending.push_back(expansion(&self.expander, url));
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+        let runtime = self.runtime.as_ref()?;
+        runtime.block_on(self.pending.next())
+    }
+}
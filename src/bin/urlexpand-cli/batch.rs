@@ -0,0 +1,263 @@
+// Expansion of many urls at once
+use std::{collections::HashMap, fs, path::Path, sync::Arc, time::Duration};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    stream::{self, BoxStream},
+    FutureExt, Stream, StreamExt,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::{
+    io::{self, AsyncBufReadExt, BufReader},
+    time,
+};
+use urlexpand::{normalize_short_url, ExpandedUrl, Expander, Result};
+
+use crate::{
+    cache::DiskCache,
+    exit::{Failures, Status},
+    journal::Journal,
+    output::Printer,
+};
+
+/// The non-empty lines of a file
+pub(crate) fn read_lines(path: &Path) -> Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// The non-empty lines of stdin, as they are read
+pub(crate) fn stdin_lines() -> impl Stream<Item = String> {
+    let lines = BufReader::new(io::stdin()).lines();
+    stream::unfold(lines, |mut lines| async move {
+        let line = lines.next_line().await.ok().flatten()?;
+        Some((line, lines))
+    })
+    .map(|line| line.trim().to_string())
+    .filter(|line| futures::future::ready(!line.is_empty()))
+}
+
+/// A progress bar on stderr, for `len` urls when known
+pub(crate) fn progress_bar(len: Option<usize>) -> ProgressBar {
+    let (bar, template) = match len {
+        Some(len) => (
+            ProgressBar::new(len as u64),
+            "{spinner} [{elapsed_precise}] {bar:30} {pos}/{len} (eta {eta}) {msg}",
+        ),
+        None => (
+            ProgressBar::new_spinner(),
+            "{spinner} [{elapsed_precise}] {pos} {msg}",
+        ),
+    };
+    if let Ok(style) = ProgressStyle::with_template(template) {
+        bar.set_style(style);
+    }
+    bar
+}
+
+/// How a batch runs, besides its urls
+pub(crate) struct Run<'a> {
+    /// How many urls are expanded at the same time
+    pub(crate) concurrency: usize,
+    /// Attempts after the first one for the urls failing with network errors
+    pub(crate) retries: u32,
+    /// Wait between the attempts
+    pub(crate) retry_delay: Duration,
+    pub(crate) progress: Option<ProgressBar>,
+    pub(crate) cache: Option<&'a DiskCache>,
+    pub(crate) journal: Option<&'a Journal>,
+    /// Expand the urls that normalize the same only once
+    pub(crate) dedupe: bool,
+}
+
+/// The result of an expansion, and whether it came from the cache
+type Expansion = Arc<(Result<ExpandedUrl>, bool)>;
+
+/// What urls normalize to when deduplicating them
+fn dedupe_key(url: &str) -> String {
+    normalize_short_url(url).map_or_else(|| url.trim().into(), String::from)
+}
+
+/// Expand `concurrency` urls at a time, printing the results in the order
+/// they complete and counting them on the progress bar, if any; the urls
+/// of the cache are not expanded again, and the new expansions are added
+/// to it, as the expanded urls are to the journal. Returns the failures
+pub(crate) async fn expand(
+    expander: &Expander,
+    urls: BoxStream<'static, String>,
+    mut printer: Printer,
+    run: Run<'_>,
+) -> Failures {
+    let Run {
+        concurrency,
+        retries,
+        retry_delay,
+        progress,
+        cache,
+        journal,
+        dedupe,
+    } = run;
+    let expand_one = |url: String| async move {
+        if let Some(expanded) = cache.and_then(|cache| cache.get(&url)) {
+            return Arc::new((Ok(expanded), true));
+        }
+        let mut result = expander.expand(&url).await;
+        for _ in 0..retries {
+            match &result {
+                Err(e) if Status::of(expander, &url, e) == Status::Network => {
+                    time::sleep(retry_delay).await;
+                    result = expander.expand(&url).await;
+                }
+                _ => break,
+            }
+        }
+        Arc::new((result, false))
+    };
+    // the expansion of every url when deduplicating, awaited by its duplicates
+    let mut seen: HashMap<String, Shared<BoxFuture<'_, Expansion>>> = HashMap::new();
+    let mut results = urls
+        .map(|url| {
+            let key = dedupe.then(|| dedupe_key(&url));
+            let (expansion, duplicate) = match key.as_ref().and_then(|key| seen.get(key)) {
+                Some(expansion) => (expansion.clone(), true),
+                None => {
+                    let expansion = expand_one(url.clone()).boxed().shared();
+                    if let Some(key) = key {
+                        seen.insert(key, expansion.clone());
+                    }
+                    (expansion, false)
+                }
+            };
+            async move { (url, expansion.await, duplicate) }
+        })
+        .buffer_unordered(concurrency.max(1));
+    let (mut successes, mut failures) = (0, Failures::default());
+    while let Some((url, expansion, duplicate)) = results.next().await {
+        let (result, cached) = &*expansion;
+        if let (Some(cache), Ok(expanded), false, false) = (cache, result, cached, duplicate) {
+            cache.insert(&url, expanded);
+        }
+        match result {
+            Ok(_) => successes += 1,
+            Err(e) => failures.add(Status::of(expander, &url, e)),
+        }
+        match &progress {
+            Some(bar) => {
+                bar.suspend(|| printer.print(&url, result));
+                bar.set_message(format!("✓ {} ✗ {}", successes, failures.count()));
+                bar.inc(1);
+            }
+            None => printer.print(&url, result),
+        }
+        // once printed, so that a resumed run has printed every url
+        if let (Some(journal), Ok(_)) = (journal, result) {
+            journal.record(&url);
+        }
+    }
+    if let Some(bar) = progress {
+        bar.finish_and_clear();
+    }
+    printer.finish();
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Colors,
+        output::{Only, Output, Verbosity},
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use urlexpand::{
+        transport::{BoxFuture, HeaderMap, HttpRequest, HttpResponse, HttpTransport, StatusCode},
+        Error,
+    };
+
+    /// Redirects `bit.ly` urls to example.com once `failing` attempts failed
+    /// to connect, counting the requests to bit.ly
+    struct Flaky {
+        failing: usize,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl HttpTransport for Flaky {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+            let response = if request.url.host_str() == Some("bit.ly") {
+                let attempt = self.requests.fetch_add(1, Ordering::SeqCst);
+                if attempt < self.failing {
+                    Err(Error::Connect("refused".into()))
+                } else {
+                    let mut headers = HeaderMap::new();
+                    headers.insert("location", "https://example.com/".parse().unwrap());
+                    Ok(HttpResponse::new(
+                        StatusCode::MOVED_PERMANENTLY,
+                        headers,
+                        "",
+                    ))
+                }
+            } else {
+                Ok(HttpResponse::new(StatusCode::OK, HeaderMap::new(), ""))
+            };
+            Box::pin(async move { response })
+        }
+    }
+
+    async fn run(urls: &[&str], failing: usize, retries: u32, dedupe: bool) -> (Failures, usize) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let expander = Expander::builder()
+            .transport(Flaky {
+                failing,
+                requests: requests.clone(),
+            })
+            .build()
+            .unwrap();
+        let urls: Vec<String> = urls.iter().map(|&url| url.into()).collect();
+        let printer = Printer::new(
+            Output::Tsv,
+            true,
+            Verbosity::Quiet,
+            Colors::default(),
+            Only::All,
+        );
+        let run = Run {
+            concurrency: 4,
+            retries,
+            retry_delay: Duration::ZERO,
+            progress: None,
+            cache: None,
+            journal: None,
+            dedupe,
+        };
+        let failures = expand(&expander, stream::iter(urls).boxed(), printer, run).await;
+        (failures, requests.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn test_dedupe_key() {
+        assert_eq!(dedupe_key("BIT.ly/abc"), "https://bit.ly/abc");
+        assert_eq!(dedupe_key("https://bit.ly/abc"), "https://bit.ly/abc");
+        assert_eq!(dedupe_key(" not a url "), "not a url");
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let (failures, requests) = run(&["https://bit.ly/a"], 2, 2, false).await;
+        assert_eq!((failures.count(), requests), (0, 3));
+        let (failures, requests) = run(&["https://bit.ly/a"], 3, 2, false).await;
+        assert_eq!((failures.status(), requests), (Status::Network, 3));
+    }
+
+    #[tokio::test]
+    async fn test_dedupe() {
+        let urls = ["https://bit.ly/a", "BIT.LY/a", "http://bit.ly/a"];
+        let (failures, requests) = run(&urls, 0, 0, true).await;
+        assert_eq!((failures.count(), requests), (0, 2));
+        let (_, requests) = run(&urls, 0, 0, false).await;
+        assert_eq!(requests, 3);
+    }
+}
@@ -0,0 +1,183 @@
+// On-disk cache of the expansions, shared by the runs of the CLI
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use urlexpand::{har::Har, ExpandedUrl, Expander, Result};
+
+use crate::{
+    exit::{Failures, Status},
+    output::Printer,
+};
+
+/// File of the cache, in its directory
+static FILE_NAME: &str = "expansions.jsonl";
+
+/// One line of the cache file
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    /// When the url was expanded, in seconds since the epoch
+    at: u64,
+    expanded: ExpandedUrl,
+}
+
+/// Successful expansions by url, kept for `ttl`
+pub(crate) struct DiskCache {
+    entries: HashMap<String, ExpandedUrl>,
+    file: Mutex<File>,
+}
+
+/// `$XDG_CACHE_HOME/urlexpand`, or `~/.cache/urlexpand`
+pub(crate) fn default_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("urlexpand"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+impl DiskCache {
+    /// Open the cache of a directory, dropping the expired entries from its
+    /// file: the live ones are written to a temporary file replacing it at
+    /// once, so a crash or a concurrent run never leaves it half written
+    pub(crate) fn open(dir: &Path, ttl: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(FILE_NAME);
+        let oldest = now().saturating_sub(ttl.as_secs());
+        let mut live: HashMap<String, Entry> = HashMap::new();
+        match fs::read_to_string(&path) {
+            Ok(lines) => {
+                for entry in lines
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+                    .filter(|entry| entry.at >= oldest)
+                {
+                    live.insert(entry.url.clone(), entry);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let compacted = dir.join(format!("{}.{}.tmp", FILE_NAME, process::id()));
+        let written =
+            write_entries(&compacted, live.values()).and_then(|()| fs::rename(&compacted, &path));
+        if let Err(e) = written {
+            fs::remove_file(&compacted).ok();
+            return Err(e.into());
+        }
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        Ok(Self {
+            entries: live
+                .into_iter()
+                .map(|(url, entry)| (url, entry.expanded))
+                .collect(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub(crate) fn get(&self, url: &str) -> Option<ExpandedUrl> {
+        self.entries.get(url).cloned()
+    }
+
+    /// Add an expansion to the file; failing to write only loses the entry
+    pub(crate) fn insert(&self, url: &str, expanded: &ExpandedUrl) {
+        let entry = Entry {
+            url: url.into(),
+            at: now(),
+            expanded: expanded.clone(),
+        };
+        if let Ok(line) = serde_json::to_string(&entry) {
+            // one write per line, not to interleave with concurrent runs
+            let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            file.write_all(format!("{}\n", line).as_bytes()).ok();
+        }
+    }
+}
+
+/// Write entries to a new file, one per line, flushed to the disk
+fn write_entries<'a>(path: &Path, entries: impl Iterator<Item = &'a Entry>) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    for entry in entries {
+        writeln!(file, "{}", serde_json::to_string(entry).unwrap_or_default())?;
+    }
+    file.into_inner()?.sync_all()
+}
+
+/// Expand the shortened urls of a recording with an expander replaying it,
+/// printing the expansions and adding them to the cache; returns the failures
+pub(crate) async fn import(
+    expander: &Expander,
+    har: &Har,
+    cache: &DiskCache,
+    mut printer: Printer,
+) -> Failures {
+    let mut urls: Vec<&str> = vec![];
+    for entry in &har.entries {
+        if expander.is_shortened(&entry.url) && !urls.contains(&entry.url.as_str()) {
+            urls.push(&entry.url);
+        }
+    }
+    let mut failures = Failures::default();
+    for url in urls {
+        let result = expander.expand(url).await;
+        match &result {
+            Ok(expanded) => cache.insert(url, expanded),
+            Err(e) => failures.add(Status::of(expander, url, e)),
+        }
+        printer.print(url, &result);
+    }
+    printer.finish();
+    failures
+}
+
+/// Remove every entry of the cache of a directory
+pub(crate) fn clear(dir: &Path) -> Result<()> {
+    match fs::remove_file(dir.join(FILE_NAME)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_open_compacts() {
+        let dir = env::temp_dir().join(format!("urlexpand-cache-{}", process::id()));
+        let expander = Expander::builder().offline(true).build().unwrap();
+        let url = "https://youtu.be/dQw4w9WgXcQ";
+        let expanded = expander.expand(url).await.unwrap();
+
+        let cache = DiskCache::open(&dir, Duration::from_secs(60)).unwrap();
+        assert!(cache.get(url).is_none());
+        cache.insert(url, &expanded);
+        cache.insert(url, &expanded);
+        drop(cache);
+        let cache = DiskCache::open(&dir, Duration::from_secs(60)).unwrap();
+        assert_eq!(cache.get(url), Some(expanded));
+        // the file was rewritten with the live entries only, in place of the old one
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let lines = fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+        assert_eq!(lines.lines().count(), 1);
+
+        clear(&dir).unwrap();
+        fs::remove_dir(&dir).unwrap();
+    }
+}
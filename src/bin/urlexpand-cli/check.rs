@@ -0,0 +1,93 @@
+// Offline classification of urls
+use serde_json::{json, Value};
+use url::Url;
+use urlexpand::{documents::csv_field, shortener_service};
+
+use crate::output::Output;
+
+/// What a url is, without any request
+pub(crate) enum Class {
+    Shortened(&'static str),
+    NotShortened,
+    Invalid,
+}
+
+impl Class {
+    pub(crate) fn of(url: &str) -> Self {
+        let parsed = Url::parse(url).or_else(|_| Url::parse(&format!("https://{}", url)));
+        match (parsed, shortener_service(url)) {
+            (Err(_), _) => Class::Invalid,
+            (Ok(parsed), _) if parsed.host().is_none() => Class::Invalid,
+            (Ok(_), Some(service)) => Class::Shortened(service),
+            (Ok(_), None) => Class::NotShortened,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Class::Shortened(_) => "shortened",
+            Class::NotShortened => "not_shortened",
+            Class::Invalid => "invalid",
+        }
+    }
+
+    fn service(&self) -> Option<&'static str> {
+        match self {
+            Class::Shortened(service) => Some(service),
+            _ => None,
+        }
+    }
+}
+
+/// Print the class of every url, in the format given (the text and TSV
+/// formats print `url<TAB>class<TAB>service`); returns whether they are all
+/// valid urls
+pub(crate) fn print(urls: &[String], output: Output) -> bool {
+    let classes: Vec<Class> = urls.iter().map(|url| Class::of(url)).collect();
+    let record = |url: &str, class: &Class| json!({"url": url, "class": class.name(), "service": class.service()});
+    match output {
+        Output::Json => {
+            let records: Vec<Value> = urls
+                .iter()
+                .zip(&classes)
+                .map(|(url, class)| record(url, class))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&records).unwrap_or_default()
+            );
+        }
+        Output::Jsonl => {
+            for (url, class) in urls.iter().zip(&classes) {
+                println!("{}", record(url, class));
+            }
+        }
+        Output::Csv => {
+            println!("url,class,service");
+            for (url, class) in urls.iter().zip(&classes) {
+                println!(
+                    "{},{},{}",
+                    csv_field(url),
+                    class.name(),
+                    class.service().unwrap_or_default()
+                );
+            }
+        }
+        Output::Text | Output::Tsv => {
+            for (url, class) in urls.iter().zip(&classes) {
+                println!(
+                    "{}\t{}\t{}",
+                    url,
+                    class.name(),
+                    class.service().unwrap_or_default()
+                );
+            }
+        }
+        Output::Map => {
+            for (url, class) in urls.iter().zip(&classes) {
+                println!("{} -> {}", url, class.name());
+            }
+        }
+    }
+    !classes.iter().any(|class| matches!(class, Class::Invalid))
+}
@@ -0,0 +1,104 @@
+// Expansion of the urls of the system clipboard
+use std::{process, thread, time::Duration};
+
+use arboard::Clipboard;
+use tokio::runtime::Runtime;
+use urlexpand::{Error, ExpandedUrl, Expander, Result};
+
+use crate::{links, output::Printer};
+
+pub(crate) fn clipboard() -> Result<Clipboard> {
+    Clipboard::new().map_err(|e| Error::StdIo(format!("clipboard: {}", e)))
+}
+
+/// The text of the clipboard
+pub(crate) fn read(clipboard: &mut Clipboard) -> Result<String> {
+    clipboard
+        .get_text()
+        .map_err(|e| Error::StdIo(format!("clipboard: {}", e)))
+}
+
+/// Replace the text of the clipboard
+pub(crate) fn write(clipboard: &mut Clipboard, text: &str) -> Result<()> {
+    clipboard
+        .set_text(text)
+        .map_err(|e| Error::StdIo(format!("clipboard: {}", e)))
+}
+
+/// The first shortened url of the text
+pub(crate) fn shortened_url(expander: &Expander, text: &str) -> Option<String> {
+    let range = links::find_shortened(expander, text).into_iter().next()?;
+    Some(text[range].into())
+}
+
+/// The text with every occurrence of the shortened url replaced by its expansion
+pub(crate) fn replaced(text: &str, url: &str, expanded: &ExpandedUrl) -> String {
+    text.replace(url, &expanded.url)
+}
+
+/// Replace the shortened urls copied to the clipboard by their expansion,
+/// checking it every `interval`, until interrupted
+pub(crate) fn watch(
+    expander: &Expander,
+    rt: &Runtime,
+    mut printer: Printer,
+    interval: Duration,
+    notify: bool,
+) -> Result<()> {
+    let mut clipboard = clipboard()?;
+    // the text of the clipboard when the watch started is left alone
+    let mut last = read(&mut clipboard).unwrap_or_default();
+    loop {
+        thread::sleep(interval);
+        // not text, such as an image
+        let Ok(text) = read(&mut clipboard) else {
+            continue;
+        };
+        if text == last {
+            continue;
+        }
+        let mut urls: Vec<&str> = links::find_shortened(expander, &text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        urls.sort_unstable();
+        urls.dedup();
+        let mut rewritten = text.clone();
+        for url in urls {
+            let result = rt.block_on(expander.expand(url));
+            printer.print(url, &result);
+            if let Ok(expanded) = result {
+                rewritten = replaced(&rewritten, url, &expanded);
+                if notify {
+                    notification(&format!("{} → {}", url, expanded.url));
+                }
+            }
+        }
+        if rewritten != text {
+            write(&mut clipboard, &rewritten)?;
+        }
+        last = rewritten;
+    }
+}
+
+/// Show a desktop notification, when the platform has a command for it
+fn notification(body: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {:?} with title \"urlexpand\"",
+            body
+        ));
+        command
+    } else {
+        let mut command = process::Command::new("notify-send");
+        command.arg("urlexpand").arg(body);
+        command
+    };
+    // the notification is a nicety: no notifier, no notification
+    command
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()
+        .ok();
+}
@@ -0,0 +1,61 @@
+// Colors of the output on a terminal
+use std::{
+    env,
+    fmt::Display,
+    io::{self, IsTerminal},
+};
+
+use anstyle::{AnsiColor, Style};
+use clap::ColorChoice;
+
+pub(crate) const ERROR: Style = AnsiColor::Red.on_default().bold();
+pub(crate) const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+pub(crate) const URL: Style = Style::new().bold();
+pub(crate) const DETAIL: Style = Style::new().dimmed();
+
+/// Whether stdout and stderr are colored
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Colors {
+    pub(crate) stdout: bool,
+    pub(crate) stderr: bool,
+}
+
+impl Colors {
+    /// With `auto`, the terminals are colored unless `NO_COLOR` is set
+    pub(crate) fn new(choice: ColorChoice) -> Self {
+        let auto = |terminal: bool| {
+            terminal && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+        };
+        match choice {
+            ColorChoice::Always => Self {
+                stdout: true,
+                stderr: true,
+            },
+            ColorChoice::Never => Self::default(),
+            ColorChoice::Auto => Self {
+                stdout: auto(io::stdout().is_terminal()),
+                stderr: auto(io::stderr().is_terminal()),
+            },
+        }
+    }
+}
+
+/// The style of an HTTP status: green for success, cyan for redirects,
+/// red for errors
+pub(crate) fn status(status: u16) -> Style {
+    match status {
+        200..=299 => AnsiColor::Green,
+        300..=399 => AnsiColor::Cyan,
+        _ => AnsiColor::Red,
+    }
+    .on_default()
+}
+
+/// `text` in `style` when `enabled`
+pub(crate) fn paint(enabled: bool, style: Style, text: impl Display) -> String {
+    if enabled {
+        format!("{}{}{:#}", style, text, style)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,71 @@
+// Defaults of the CLI read from a configuration file
+use std::{env, fs, io, path::PathBuf};
+
+use serde::Deserialize;
+use urlexpand::{Error, ExpanderConfig, Result};
+
+use crate::output::Output;
+
+/// `config.toml`: the settings of the expander, plus those of the CLI
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct CliConfig {
+    /// Output format when `--output` is not given
+    pub(crate) output: Option<Output>,
+    #[serde(flatten)]
+    pub(crate) expander: ExpanderConfig,
+}
+
+/// `$XDG_CONFIG_HOME/urlexpand/config.toml`, or `~/.config/urlexpand/config.toml`
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("urlexpand").join("config.toml"))
+}
+
+/// Read the configuration file given, or the default one when it exists
+pub(crate) fn load(path: Option<PathBuf>) -> Result<CliConfig> {
+    let (path, required) = match path {
+        Some(path) => (path, true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(CliConfig::default()),
+        },
+    };
+    let toml = match fs::read_to_string(&path) {
+        Ok(toml) => toml,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+            return Ok(CliConfig::default())
+        }
+        Err(e) => return Err(Error::Config(format!("{}: {}", path.display(), e))),
+    };
+    toml::from_str(&toml).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_load() {
+        let path = env::temp_dir().join(format!("urlexpand-config-{}.toml", process::id()));
+        fs::write(
+            &path,
+            "output = \"json\"\ntimeout = 5\n\n[services]\ncustom = [\"go.example\"]\n",
+        )
+        .unwrap();
+        let config = load(Some(path.clone())).unwrap();
+        assert!(config.output == Some(Output::Json));
+        assert_eq!(config.expander.timeout, Some(5.0));
+        assert_eq!(config.expander.services.custom, ["go.example"]);
+
+        fs::write(&path, "output = \"yaml\"\n").unwrap();
+        assert!(matches!(load(Some(path.clone())), Err(Error::Config(_))));
+        fs::remove_file(&path).unwrap();
+        // a file given must exist
+        assert!(matches!(load(Some(path)), Err(Error::Config(_))));
+    }
+}
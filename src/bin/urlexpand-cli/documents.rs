@@ -0,0 +1,43 @@
+// Output of the commands expanding the links of a whole document
+use std::{fs, path::Path};
+
+use urlexpand::{documents::ExpandedDocument, Expander};
+
+use crate::{
+    exit::{Failures, Status},
+    output::Printer,
+};
+
+/// Write the expanded document to `out` and print its expansions, or print
+/// the document itself when there is no `out`, with its failures on stderr
+pub(crate) fn write(
+    expander: &Expander,
+    expanded: ExpandedDocument,
+    out: Option<&Path>,
+    mut printer: Printer,
+) -> urlexpand::Result<Failures> {
+    let mut failures = Failures::default();
+    for expansion in &expanded.expansions {
+        if let Err(e) = &expansion.result {
+            failures.add(Status::of(expander, &expansion.original, e));
+        }
+    }
+    match out {
+        Some(out) => {
+            fs::write(out, &expanded.document)?;
+            for expansion in &expanded.expansions {
+                printer.print(&expansion.original, &expansion.result);
+            }
+            printer.finish();
+        }
+        None => {
+            print!("{}", expanded.document);
+            for expansion in &expanded.expansions {
+                if let Err(e) = &expansion.result {
+                    eprintln!("{}: {}", expansion.original, e);
+                }
+            }
+        }
+    }
+    Ok(failures)
+}
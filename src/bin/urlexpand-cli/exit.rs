@@ -0,0 +1,162 @@
+// Exit statuses of the CLI, as documented in its `--help`
+use std::process::ExitCode;
+
+use urlexpand::{Error, Expander};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    /// Every url was expanded
+    Success = 0,
+    /// Some urls could not be expanded
+    Failures = 1,
+    /// Invalid arguments or configuration, or urls that are not shortened
+    InvalidInput = 2,
+    /// Connection, DNS or timeout errors
+    Network = 3,
+}
+
+impl Status {
+    /// The status of a failed expansion of `url`
+    pub(crate) fn of(expander: &Expander, url: &str, error: &Error) -> Self {
+        match error {
+            Error::NoString if !expander.is_shortened(url) => Status::InvalidInput,
+            Error::DisallowedScheme(_) | Error::ServiceDenied(_) | Error::Config(_) => {
+                Status::InvalidInput
+            }
+            Error::Timeout { .. }
+            | Error::Connect(_)
+            | Error::Dns(_)
+            | Error::Reqwest(_)
+            | Error::ServiceUnavailable(_)
+            | Error::ServiceBlocked { .. }
+            | Error::BotChallenge(_) => Status::Network,
+            _ => Status::Failures,
+        }
+    }
+
+    /// The status of an error preventing any expansion
+    pub(crate) fn of_run(error: &Error) -> Self {
+        match error {
+            Error::Config(_) | Error::StdIo(_) => Status::InvalidInput,
+            _ => Status::Failures,
+        }
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+/// The failures of a run: its status is `InvalidInput` or `Network` when
+/// every failure is of that kind, `Failures` when they are mixed
+#[derive(Debug, Default)]
+pub(crate) struct Failures {
+    count: usize,
+    status: Option<Status>,
+}
+
+impl Failures {
+    pub(crate) fn add(&mut self, status: Status) {
+        self.count += 1;
+        self.status = Some(match self.status {
+            Some(previous) if previous != status => Status::Failures,
+            _ => status,
+        });
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    pub(crate) fn status(&self) -> Status {
+        self.status.unwrap_or(Status::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use urlexpand::TimeoutStage;
+
+    #[test]
+    fn test_status_of() {
+        let expander = Expander::builder().build().unwrap();
+        let status = |url: &str, error: Error| Status::of(&expander, url, &error);
+        assert_eq!(
+            status("https://example.com/", Error::NoString),
+            Status::InvalidInput
+        );
+        assert_eq!(
+            status("https://bit.ly/abc", Error::NoString),
+            Status::Failures
+        );
+        assert_eq!(
+            status("ftp://bit.ly/abc", Error::DisallowedScheme("ftp".into())),
+            Status::InvalidInput
+        );
+        assert_eq!(
+            status("https://bit.ly/abc", Error::ServiceDenied("bit.ly".into())),
+            Status::InvalidInput
+        );
+        let timeout = Error::Timeout {
+            elapsed: Duration::from_secs(10),
+            stage: TimeoutStage::Total,
+        };
+        assert_eq!(status("https://bit.ly/abc", timeout), Status::Network);
+        assert_eq!(
+            status("https://bit.ly/abc", Error::Connect("refused".into())),
+            Status::Network
+        );
+        assert_eq!(
+            status("https://bit.ly/abc", Error::Dns("no such host".into())),
+            Status::Network
+        );
+        assert_eq!(
+            status("https://surl.li/abc", Error::BotChallenge("surl.li".into())),
+            Status::Network
+        );
+        let gone = Error::LinkGone {
+            service: "bit.ly".into(),
+        };
+        assert_eq!(status("https://bit.ly/abc", gone), Status::Failures);
+    }
+
+    #[test]
+    fn test_status_of_run() {
+        assert_eq!(
+            Status::of_run(&Error::Config("bad".into())),
+            Status::InvalidInput
+        );
+        assert_eq!(
+            Status::of_run(&Error::StdIo("no such file".into())),
+            Status::InvalidInput
+        );
+        assert_eq!(Status::of_run(&Error::Unknown), Status::Failures);
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(ExitCode::from(Status::Success), ExitCode::from(0));
+        assert_eq!(ExitCode::from(Status::Failures), ExitCode::from(1));
+        assert_eq!(ExitCode::from(Status::InvalidInput), ExitCode::from(2));
+        assert_eq!(ExitCode::from(Status::Network), ExitCode::from(3));
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut failures = Failures::default();
+        assert_eq!(failures.status(), Status::Success);
+        failures.add(Status::Network);
+        failures.add(Status::Network);
+        assert_eq!(failures.status(), Status::Network);
+        assert_eq!(failures.count(), 2);
+        // mixed failures
+        failures.add(Status::InvalidInput);
+        assert_eq!(failures.status(), Status::Failures);
+        failures.add(Status::InvalidInput);
+        assert_eq!(failures.status(), Status::Failures);
+    }
+}
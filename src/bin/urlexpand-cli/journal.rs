@@ -0,0 +1,104 @@
+// Journal of the urls of a file already expanded, to resume interrupted runs
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use urlexpand::Result;
+
+/// The urls expanded so far, one per line, in a file next to the input
+pub(crate) struct Journal {
+    path: PathBuf,
+    done: HashSet<String>,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// `urls.txt.journal` for `urls.txt`
+    pub(crate) fn path_of(input: &Path) -> PathBuf {
+        let mut name = input.as_os_str().to_owned();
+        name.push(".journal");
+        name.into()
+    }
+
+    /// The journal of a run over `input`: a new one, or the one of the
+    /// interrupted run when resuming
+    pub(crate) fn open(input: &Path, resume: bool) -> Result<Self> {
+        let path = Self::path_of(input);
+        let done = match (resume, fs::read_to_string(&path)) {
+            (true, Ok(lines)) => lines.lines().map(String::from).collect(),
+            (true, Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => HashSet::new(),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            done,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Whether an earlier run expanded the url
+    pub(crate) fn is_done(&self, url: &str) -> bool {
+        self.done.contains(url)
+    }
+
+    /// Note that the url was expanded; failing to write only loses the entry
+    pub(crate) fn record(&self, url: &str) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        writeln!(file, "{}", url).ok();
+    }
+
+    /// Remove the journal of a run that expanded every url
+    pub(crate) fn complete(self) -> Result<()> {
+        drop(self.file);
+        Ok(fs::remove_file(self.path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn test_path_of() {
+        assert_eq!(
+            Journal::path_of(Path::new("lists/urls.txt")),
+            PathBuf::from("lists/urls.txt.journal")
+        );
+    }
+
+    #[test]
+    fn test_resume() {
+        let input = env::temp_dir().join(format!("urlexpand-journal-{}.txt", process::id()));
+        let journal = Journal::open(&input, false).unwrap();
+        journal.record("https://bit.ly/a");
+        journal.record("https://bit.ly/b");
+        drop(journal);
+
+        // an interrupted run is resumed where it stopped
+        let journal = Journal::open(&input, true).unwrap();
+        assert!(journal.is_done("https://bit.ly/a"));
+        assert!(!journal.is_done("https://bit.ly/c"));
+        journal.record("https://bit.ly/c");
+        drop(journal);
+        let journal = Journal::open(&input, true).unwrap();
+        assert!(journal.is_done("https://bit.ly/b") && journal.is_done("https://bit.ly/c"));
+        journal.complete().unwrap();
+        assert!(!Journal::path_of(&input).exists());
+
+        // a new run starts over
+        let journal = Journal::open(&input, false).unwrap();
+        assert!(!journal.is_done("https://bit.ly/a"));
+        journal.complete().unwrap();
+    }
+}
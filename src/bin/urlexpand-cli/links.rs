@@ -0,0 +1,30 @@
+// Urls found in text
+use std::ops::Range;
+
+use regex::Regex;
+use urlexpand::Expander;
+
+/// Where the http(s) urls of `text` are, without the punctuation ending a
+/// sentence or closing a Markdown link after them
+pub(crate) fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let Ok(pattern) = Regex::new(r#"(?i)\bhttps?://[^\s<>"'`\[\]{}|\\^]+"#) else {
+        return vec![];
+    };
+    pattern
+        .find_iter(text)
+        .map(|found| {
+            let url = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            found.start()..found.start() + url.len()
+        })
+        .collect()
+}
+
+/// Where the urls of `text` the expander takes for shortened urls are
+pub(crate) fn find_shortened(expander: &Expander, text: &str) -> Vec<Range<usize>> {
+    find_urls(text)
+        .into_iter()
+        .filter(|range| expander.is_shortened(&text[range.clone()]))
+        .collect()
+}
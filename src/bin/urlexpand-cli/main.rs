@@ -0,0 +1,796 @@
+// Command line interface of urlexpand
+mod batch;
+mod cache;
+mod check;
+#[cfg(feature = "clipboard")]
+mod clip;
+mod color;
+mod config;
+mod documents;
+mod exit;
+mod journal;
+mod links;
+mod output;
+mod ratelimit;
+mod repl;
+mod scan;
+mod serve;
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::{Args, ColorChoice, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use futures::{
+    io::{AllowStdIo, BufReader},
+    stream, StreamExt,
+};
+use tokio::runtime::Runtime;
+use urlexpand::{
+    documents::{expand_bookmarks, expand_csv, expand_sitemap},
+    har::{Har, HarRecorder},
+    is_shortened, strip_tracking, Config, ExpandedUrl, Expander, ExpanderBuilder,
+};
+
+use color::{paint, Colors};
+use exit::{Failures, Status};
+use output::{Only, Output, Printer, Verbosity};
+
+/// Expand / Unshorten shortened URLs
+#[derive(Parser)]
+#[command(
+    name = "urlexpand-cli",
+    version,
+    about,
+    after_help = "Without a command (with stdin piped), or with `-` as command, \
+                  the urls of stdin are expanded: `urlexpand-cli - -o json`\n\n\
+                  Exit status:\n  \
+                  0  every url was expanded (`check`: the url is shortened)\n  \
+                  1  some urls could not be expanded (`check`: not shortened)\n  \
+                  2  invalid input: arguments, configuration, or urls that are not shortened\n  \
+                  3  network error: every failure was a connection, DNS or timeout error"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// When to color the output: `auto` colors terminals, unless `NO_COLOR` is set
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Expand a shortened url, or every line of a file or of stdin
+    Expand {
+        /// The shortened url, or `-` for the lines of stdin
+        #[arg(required_unless_present = "file")]
+        url: Option<String>,
+        /// Expand every line of this file, printing the results as they come
+        #[arg(long, conflicts_with = "url")]
+        file: Option<PathBuf>,
+        /// How many urls of the file to expand at the same time
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        /// Do not show a progress bar for files and stdin (never shown
+        /// when stdout is not a terminal)
+        #[arg(long)]
+        no_progress: bool,
+        /// Keep the expansions in this directory, and do not expand again
+        /// the urls already there [default: ~/.cache/urlexpand]
+        #[arg(long, value_name = "DIR")]
+        cache: Option<Option<PathBuf>>,
+        /// How long the cached expansions are kept, in seconds
+        #[arg(long, default_value_t = 7 * 24 * 60 * 60, requires = "cache")]
+        cache_ttl: u64,
+        /// Try the urls failing with network errors this many times again
+        #[arg(long, default_value_t = 0)]
+        retries: u32,
+        /// Wait between the attempts, such as `500ms`, `2s` or `1m`
+        #[arg(long, default_value = "1s", value_parser = parse_duration)]
+        retry_delay: Duration,
+        /// Expand the urls that are the same once normalized (scheme, case
+        /// of the host) only once, printing the result for every one of them
+        #[arg(long)]
+        dedupe: bool,
+        /// Note the urls of the file expanded so far in a journal
+        /// (`FILE.journal`), removed once every url was expanded
+        #[arg(long, requires = "file")]
+        journal: bool,
+        /// Skip the urls of the file an interrupted run already expanded,
+        /// as noted in its journal; implies `--journal`
+        #[arg(long, requires = "file")]
+        resume: bool,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand a shortened url, printing every hop with its status and timing
+    Chain {
+        /// The shortened url
+        url: String,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand a shortened url and strip the tracking parameters of its destination
+    Clean {
+        /// The shortened url
+        url: String,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand the shortened url found in the clipboard
+    #[cfg(feature = "clipboard")]
+    Clip {
+        /// Write the text of the clipboard back, with the expanded url
+        #[arg(long)]
+        replace: bool,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Replace the shortened urls copied to the clipboard by their expansion,
+    /// until interrupted
+    #[cfg(feature = "clipboard")]
+    WatchClipboard {
+        /// How often the clipboard is checked, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+        /// Do not show a desktop notification for every replaced url
+        #[arg(long)]
+        no_notify: bool,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Find the shortened urls of text, Markdown and HTML files, and
+    /// expand them in place with `--write`
+    Scan {
+        /// A file, or a directory to scan recursively
+        path: PathBuf,
+        /// Replace the urls by their expansion, printing a diff of the changes
+        #[arg(long)]
+        write: bool,
+        /// How many urls to expand at the same time
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand the shortened urls of a bookmarks file exported by a browser
+    /// (Netscape HTML), printing the updated file
+    Bookmarks {
+        /// The exported bookmarks
+        file: PathBuf,
+        /// Write the updated bookmarks to this file, and print the expansions
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand the shortened `<loc>` urls of a sitemap, printing the
+    /// corrected sitemap
+    Sitemap {
+        /// The sitemap.xml (or sitemap index)
+        file: PathBuf,
+        /// Write the corrected sitemap to this file, and print the expansions
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Expand the urls of a column of a CSV file, appending an
+    /// `expanded_url` and an `error` column to its rows
+    Csv {
+        /// The CSV file, with a header row
+        file: PathBuf,
+        /// The column of the urls, by its name in the header
+        #[arg(long, default_value = "url")]
+        column: String,
+        /// Write the expanded CSV to this file rather than to stdout
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+        /// How many urls to expand at the same time
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Serve `GET /expand?url=...` and `POST /expand` (a JSON array of urls)
+    /// over HTTP, answering JSON records, and `GET /metrics` for Prometheus
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// How many expansions are kept in memory
+        #[arg(long, default_value_t = 10_000)]
+        cache_size: usize,
+        /// How long the expansions are kept in memory, in seconds
+        #[arg(long, default_value_t = 3600)]
+        cache_ttl: u64,
+        /// How many expansions may run at the same time, over every request
+        #[arg(long, default_value_t = 64)]
+        max_in_flight: usize,
+        /// How many urls of a batch are expanded at the same time
+        #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+        concurrency: u16,
+        /// Requests (or urls of batches) a client address may send per minute
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        rate_limit: Option<u32>,
+        /// Require `Authorization: Bearer TOKEN` on every request
+        #[arg(long, env = "URLEXPAND_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+        /// Let the short urls of clients reach loopback, private and
+        /// link-local addresses, refused by default
+        #[arg(long)]
+        allow_private_addresses: bool,
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Check whether urls are shortened urls, without any request
+    Check {
+        /// The url to check
+        #[arg(required_unless_present = "file")]
+        url: Option<String>,
+        /// Classify every line of this file as shortened (with its service),
+        /// not shortened or invalid
+        #[arg(long, conflicts_with = "url")]
+        file: Option<PathBuf>,
+        /// How to print the classes [default: csv for a file, the class otherwise]
+        #[arg(short, long, value_enum)]
+        output: Option<Output>,
+    },
+    /// Expand and check urls interactively
+    Repl {
+        #[command(flatten)]
+        options: Options,
+    },
+    /// Manage the cache of `expand --cache`
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print the completion script of a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Remove every cached expansion
+    Clear {
+        /// Directory of the cache [default: ~/.cache/urlexpand]
+        #[arg(long, value_name = "DIR")]
+        cache: Option<PathBuf>,
+    },
+    /// Expand the shortened urls of a HAR file or cassette by replaying
+    /// its responses, without any request, and add them to the cache, so
+    /// that the runs over the same urls are offline and reproducible
+    Import {
+        /// The HAR file (`--har`, browser devtools) or cassette
+        file: PathBuf,
+        /// Directory of the cache [default: ~/.cache/urlexpand]
+        #[arg(long, value_name = "DIR")]
+        cache: Option<PathBuf>,
+        /// How long the cached expansions are kept, in seconds
+        #[arg(long, default_value_t = 7 * 24 * 60 * 60)]
+        cache_ttl: u64,
+        #[command(flatten)]
+        options: Box<Options>,
+    },
+}
+
+impl Command {
+    fn options_mut(&mut self) -> Option<&mut Options> {
+        match self {
+            Command::Expand { options, .. }
+            | Command::Chain { options, .. }
+            | Command::Clean { options, .. }
+            | Command::Scan { options, .. }
+            | Command::Bookmarks { options, .. }
+            | Command::Sitemap { options, .. }
+            | Command::Csv { options, .. }
+            | Command::Serve { options, .. }
+            | Command::Repl { options } => Some(options),
+            Command::Cache {
+                action: CacheAction::Import { options, .. },
+            } => Some(options),
+            #[cfg(feature = "clipboard")]
+            Command::Clip { options, .. } | Command::WatchClipboard { options, .. } => {
+                Some(options)
+            }
+            Command::Check { .. } | Command::Cache { .. } | Command::Completions { .. } => None,
+        }
+    }
+}
+
+/// Timeout of the expansions when neither configured nor given
+static DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options of the commands that expand urls
+#[derive(Args)]
+struct Options {
+    /// Timeout of every expansion, in seconds [default: 10]
+    #[arg(long)]
+    timeout: Option<f64>,
+    /// How to print the results [default: tsv for a file, text otherwise]
+    #[arg(short, long, value_enum)]
+    output: Option<Output>,
+    /// Print only the expanded urls: no errors, hops or progress bar
+    #[arg(short, long, conflicts_with_all = ["output", "verbose"])]
+    quiet: bool,
+    /// Print `original -> expanded` pairs, even with `--quiet`, to join the
+    /// results back to the inputs
+    #[arg(long, conflicts_with = "output")]
+    map: bool,
+    /// Print only the expanded urls, leaving the failures out
+    #[arg(long, conflicts_with = "only_failed")]
+    only_success: bool,
+    /// Print only the failures, on stdout, for reports of the urls that
+    /// could not be expanded
+    #[arg(long)]
+    only_failed: bool,
+    /// Print every hop and its time to stderr; twice for the details of the time
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Proxy url every request goes through (`http://`, `https://` or `socks5://`)
+    #[arg(long)]
+    proxy: Option<String>,
+    /// `User-Agent` sent to the shorteners
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// Redirects followed before giving up
+    #[arg(long)]
+    max_redirects: Option<usize>,
+    /// Accept invalid TLS certificates
+    #[arg(long)]
+    no_verify_tls: bool,
+    /// Configuration file [default: ~/.config/urlexpand/config.toml]
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Write every request made to this HTTP Archive (HAR) file when the
+    /// command ends, for browser devtools and the other HAR tools
+    #[arg(long, value_name = "PATH")]
+    har: Option<PathBuf>,
+    /// The recorder of `--har`
+    #[arg(skip)]
+    recorder: Option<HarRecorder>,
+    /// The configuration file, once read
+    #[arg(skip)]
+    file: config::CliConfig,
+    /// The colors of `--color`
+    #[arg(skip)]
+    colors: Colors,
+}
+
+impl Options {
+    /// Read the configuration file
+    fn load_config(&mut self) -> urlexpand::Result<()> {
+        self.file = config::load(self.config.take())?;
+        Ok(())
+    }
+
+    /// An expander with the settings of the configuration file, overridden
+    /// by the `URLEXPAND_*` variables, then by the options
+    fn expander(&self) -> urlexpand::Result<Expander> {
+        self.builder()?.build()
+    }
+
+    /// The builder of `expander`, for commands with settings of their own
+    fn builder(&self) -> urlexpand::Result<ExpanderBuilder> {
+        let mut builder = Expander::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .expander_config(&self.file.expander)?
+            .config(&Config::from_env()?)
+            .config(&Config {
+                timeout: None,
+                proxy: self.proxy.clone(),
+                user_agent: self.user_agent.clone(),
+                max_redirects: self.max_redirects,
+            })
+            .verify_tls(!self.no_verify_tls);
+        if let Some(recorder) = &self.recorder {
+            builder = builder.middleware(recorder.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(
+                Duration::try_from_secs_f64(timeout)
+                    .map_err(|_| urlexpand::Error::Config(format!("--timeout {}", timeout)))?,
+            );
+        }
+        Ok(builder)
+    }
+
+    /// A printer of the results, in the format given, configured or else
+    /// `default`; quiet runs print text, unless mapping
+    fn printer(&self, default: Output, many: bool) -> Printer {
+        let verbosity = match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Hops,
+            (false, _) => Verbosity::Timings,
+        };
+        let output = match verbosity {
+            _ if self.map => Output::Map,
+            Verbosity::Quiet => Output::Text,
+            _ => self.output.or(self.file.output).unwrap_or(default),
+        };
+        let only = match (self.only_success, self.only_failed) {
+            (true, _) => Only::Success,
+            (_, true) => Only::Failed,
+            _ => Only::All,
+        };
+        Printer::new(output, many, verbosity, self.colors, only)
+    }
+}
+
+/// The arguments, with `expand -` when reading stdin without a command
+fn args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    if args.len() == 1 && !io::stdin().is_terminal() {
+        args.push("-".into());
+    }
+    if args.get(1).is_some_and(|arg| arg == "-") {
+        args.insert(1, "expand".into());
+    }
+    args
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_from(args());
+    let colors = Colors::new(cli.color);
+    match run(cli, colors) {
+        Ok(code) => code,
+        Err(e) => {
+            // the message of an I/O error says more than its display
+            let message = match &e {
+                urlexpand::Error::StdIo(message) => message.clone(),
+                e => e.to_string(),
+            };
+            eprintln!(
+                "{} {}",
+                paint(colors.stderr, color::ERROR, "error:"),
+                message
+            );
+            Status::of_run(&e).into()
+        }
+    }
+}
+
+/// A duration such as `500ms`, `2s`, `1m`, or a number of seconds
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration: {}", duration))?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        unit => return Err(format!("unknown unit {:?}: use ms, s, m or h", unit)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+/// The cache directory given, or else the default one
+fn cache_dir(dir: Option<PathBuf>) -> urlexpand::Result<PathBuf> {
+    dir.or_else(cache::default_dir)
+        .ok_or_else(|| urlexpand::Error::Config("no cache directory: use --cache DIR".into()))
+}
+
+/// The exit status of the expansion of a single url
+fn exit_code(expander: &Expander, url: &str, result: &urlexpand::Result<ExpandedUrl>) -> ExitCode {
+    let mut failures = Failures::default();
+    if let Err(e) = result {
+        failures.add(Status::of(expander, url, e));
+    }
+    failures.status().into()
+}
+
+fn run(mut cli: Cli, colors: Colors) -> urlexpand::Result<ExitCode> {
+    let mut har = None;
+    if let Some(options) = cli.command.options_mut() {
+        options.colors = colors;
+        options.load_config()?;
+        if let Some(path) = options.har.clone() {
+            let recorder = HarRecorder::new();
+            options.recorder = Some(recorder.clone());
+            har = Some((path, recorder));
+        }
+    }
+    let result = run_command(cli.command);
+    if let Some((path, recorder)) = har {
+        recorder.har().save(path)?;
+    }
+    result
+}
+
+fn run_command(command: Command) -> urlexpand::Result<ExitCode> {
+    match command {
+        Command::Expand {
+            url,
+            file,
+            concurrency,
+            no_progress,
+            cache,
+            cache_ttl,
+            retries,
+            retry_delay,
+            dedupe,
+            journal,
+            resume,
+            options,
+        } => {
+            let expander = options.expander()?;
+            let cache = cache
+                .map(|dir| cache::DiskCache::open(&cache_dir(dir)?, Duration::from_secs(cache_ttl)))
+                .transpose()?;
+            let show_progress = !no_progress && !options.quiet && io::stdout().is_terminal();
+            let journal = file
+                .as_deref()
+                .filter(|_| journal || resume)
+                .map(|file| journal::Journal::open(file, resume))
+                .transpose()?;
+            let (urls, printer, progress) = match (&file, url) {
+                (Some(file), _) => {
+                    let mut urls = batch::read_lines(file)?;
+                    if let Some(journal) = &journal {
+                        urls.retain(|url| !journal.is_done(url));
+                    }
+                    let progress = show_progress.then(|| batch::progress_bar(Some(urls.len())));
+                    (
+                        stream::iter(urls).boxed(),
+                        options.printer(Output::Tsv, true),
+                        progress,
+                    )
+                }
+                (None, Some(url)) if url == "-" => (
+                    batch::stdin_lines().boxed(),
+                    options.printer(Output::Text, true),
+                    show_progress.then(|| batch::progress_bar(None)),
+                ),
+                (None, url) => (
+                    stream::iter(url).boxed(),
+                    options.printer(Output::Text, false),
+                    None,
+                ),
+            };
+            let run = batch::Run {
+                concurrency: concurrency.into(),
+                retries,
+                retry_delay,
+                progress,
+                cache: cache.as_ref(),
+                journal: journal.as_ref(),
+                dedupe,
+            };
+            let failures = Runtime::new()?.block_on(batch::expand(&expander, urls, printer, run));
+            if let (Some(journal), 0) = (journal, failures.count()) {
+                journal.complete()?;
+            }
+            Ok(failures.status().into())
+        }
+        Command::Chain { url, options } => {
+            let expander = options.expander()?;
+            let result = Runtime::new()?.block_on(expander.expand(&url));
+            let mut printer = options.printer(Output::Text, false);
+            printer.print_chain(&url, &result);
+            printer.finish();
+            Ok(exit_code(&expander, &url, &result))
+        }
+        Command::Clean { url, options } => {
+            let expander = options.expander()?;
+            let result = Runtime::new()?
+                .block_on(expander.expand(&url))
+                .map(|mut expanded| {
+                    expanded.url = strip_tracking(&expanded.url);
+                    expanded
+                });
+            let mut printer = options.printer(Output::Text, false);
+            printer.print(&url, &result);
+            printer.finish();
+            Ok(exit_code(&expander, &url, &result))
+        }
+        #[cfg(feature = "clipboard")]
+        Command::Clip { replace, options } => {
+            let expander = options.expander()?;
+            let mut clipboard = clip::clipboard()?;
+            let text = clip::read(&mut clipboard)?;
+            let Some(url) = clip::shortened_url(&expander, &text) else {
+                eprintln!("no shortened url in the clipboard");
+                return Ok(Status::InvalidInput.into());
+            };
+            let result = Runtime::new()?.block_on(expander.expand(&url));
+            let mut printer = options.printer(Output::Text, false);
+            printer.print(&url, &result);
+            printer.finish();
+            if let (true, Ok(expanded)) = (replace, &result) {
+                clip::write(&mut clipboard, &clip::replaced(&text, &url, expanded))?;
+            }
+            Ok(exit_code(&expander, &url, &result))
+        }
+        #[cfg(feature = "clipboard")]
+        Command::WatchClipboard {
+            interval,
+            no_notify,
+            options,
+        } => {
+            clip::watch(
+                &options.expander()?,
+                &Runtime::new()?,
+                options.printer(Output::Tsv, true),
+                Duration::from_millis(interval),
+                !no_notify,
+            )?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Scan {
+            path,
+            write,
+            concurrency,
+            options,
+        } => {
+            let expander = options.expander()?;
+            let files = scan::files(&path)?;
+            if !write {
+                scan::report(&expander, &files)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            let failures = Runtime::new()?.block_on(scan::rewrite(
+                &expander,
+                &files,
+                concurrency.into(),
+                options.colors,
+            ))?;
+            Ok(failures.status().into())
+        }
+        Command::Bookmarks { file, out, options } => {
+            let expander = options.expander()?;
+            let html = fs::read_to_string(&file)?;
+            let expanded = Runtime::new()?.block_on(expand_bookmarks(&expander, &html));
+            let printer = options.printer(Output::Tsv, true);
+            let failures = documents::write(&expander, expanded, out.as_deref(), printer)?;
+            Ok(failures.status().into())
+        }
+        Command::Sitemap { file, out, options } => {
+            let expander = options.expander()?;
+            let xml = fs::read_to_string(&file)?;
+            let expanded = Runtime::new()?.block_on(expand_sitemap(&expander, &xml));
+            let printer = options.printer(Output::Tsv, true);
+            let failures = documents::write(&expander, expanded, out.as_deref(), printer)?;
+            Ok(failures.status().into())
+        }
+        Command::Csv {
+            file,
+            column,
+            out,
+            concurrency,
+            options,
+        } => {
+            let expander = options.expander()?;
+            let reader = BufReader::new(AllowStdIo::new(fs::File::open(&file)?));
+            let writer: Box<dyn Write> = match out {
+                Some(out) => Box::new(io::BufWriter::new(fs::File::create(out)?)),
+                None => Box::new(io::stdout().lock()),
+            };
+            let summary = Runtime::new()?.block_on(expand_csv(
+                &expander,
+                reader,
+                AllowStdIo::new(writer),
+                &column,
+                concurrency.into(),
+            ))?;
+            let mut failures = Failures::default();
+            for failure in &summary.failures {
+                if let Err(e) = &failure.result {
+                    failures.add(Status::of(&expander, &failure.original, e));
+                }
+            }
+            Ok(failures.status().into())
+        }
+        Command::Serve {
+            listen,
+            cache_size,
+            cache_ttl,
+            max_in_flight,
+            concurrency,
+            rate_limit,
+            token,
+            allow_private_addresses,
+            options,
+        } => {
+            let expander = options
+                .builder()?
+                .cache(cache_size, Duration::from_secs(cache_ttl))
+                .max_in_flight(max_in_flight)
+                .block_private_addresses(!allow_private_addresses)
+                .build()?;
+            let access = serve::Access { token, rate_limit };
+            Runtime::new()?.block_on(serve::run(expander, concurrency.into(), access, listen))?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Check {
+            url: Some(url),
+            file: None,
+            output: None,
+        } => Ok(if is_shortened(&url) {
+            println!("shortened");
+            ExitCode::SUCCESS
+        } else {
+            println!("not shortened");
+            ExitCode::FAILURE
+        }),
+        Command::Check { url, file, output } => {
+            let urls = match file {
+                Some(file) => batch::read_lines(&file)?,
+                None => url.into_iter().collect(),
+            };
+            Ok(if check::print(&urls, output.unwrap_or(Output::Csv)) {
+                ExitCode::SUCCESS
+            } else {
+                Status::InvalidInput.into()
+            })
+        }
+        Command::Repl { options } => {
+            repl::run(&options.expander()?, &Runtime::new()?)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Cache {
+            action: CacheAction::Clear { cache },
+        } => {
+            cache::clear(&cache_dir(cache)?)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Cache {
+            action:
+                CacheAction::Import {
+                    file,
+                    cache,
+                    cache_ttl,
+                    options,
+                },
+        } => {
+            let har = Har::load(&file)?;
+            let expander = options.builder()?.transport(har.clone()).build()?;
+            let cache = cache::DiskCache::open(&cache_dir(cache)?, Duration::from_secs(cache_ttl))?;
+            let printer = options.printer(Output::Tsv, true);
+            let failures =
+                Runtime::new()?.block_on(cache::import(&expander, &har, &cache, printer));
+            Ok(failures.status().into())
+        }
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration(" 2 m "), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+// Printing of expansion results
+use clap::ValueEnum;
+use serde_json::{json, Value};
+use urlexpand::{documents::csv_field, ExpandedUrl, HopInfo, Result};
+
+use crate::color::{self, paint, Colors};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Output {
+    /// The expanded url
+    Text,
+    /// The original and the expanded url, separated by a tab
+    Tsv,
+    /// A JSON record, or an array of them for a file
+    Json,
+    /// One JSON record per line
+    Jsonl,
+    /// Comma separated values, with a header row
+    Csv,
+    /// `original -> expanded` pairs
+    Map,
+}
+
+/// What is printed besides the results
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Verbosity {
+    /// Only the expanded urls, not even the errors
+    Quiet,
+    Normal,
+    /// Every hop, with its status and total time, on stderr
+    Hops,
+    /// Every hop with the details of its timing and the service, on stderr
+    Timings,
+}
+
+/// Which results are printed
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Only {
+    All,
+    /// The expanded urls, the failures being left out
+    Success,
+    /// The failures, on stdout: their original urls in the text format,
+    /// with the error in the TSV and map formats
+    Failed,
+}
+
+/// Columns of the CSV output
+static CSV_HEADER: [&str; 5] = ["original", "url", "service", "status", "error"];
+
+/// Prints the results of a run in one of the formats; failures go to
+/// stderr, except in the JSON and CSV formats where they are records too
+pub(crate) struct Printer {
+    output: Output,
+    /// Whether the run expands more than one url
+    many: bool,
+    verbosity: Verbosity,
+    colors: Colors,
+    only: Only,
+    records: Vec<Value>,
+}
+
+impl Printer {
+    pub(crate) fn new(
+        output: Output,
+        many: bool,
+        verbosity: Verbosity,
+        colors: Colors,
+        only: Only,
+    ) -> Self {
+        if output == Output::Csv {
+            println!("{}", CSV_HEADER.join(","));
+        }
+        Self {
+            output,
+            many,
+            verbosity,
+            colors,
+            only,
+            records: vec![],
+        }
+    }
+
+    pub(crate) fn print(&mut self, original: &str, result: &Result<ExpandedUrl>) {
+        if let (Only::Success, Err(_)) | (Only::Failed, Ok(_)) = (self.only, result) {
+            return;
+        }
+        if let Ok(expanded) = result {
+            self.print_hops(original, expanded);
+            self.warn(original, expanded);
+        }
+        match (self.output, result) {
+            (Output::Json, _) => self.records.push(record(original, result)),
+            (Output::Jsonl, _) => println!("{}", record(original, result)),
+            (Output::Csv, _) => println!("{}", csv_row(original, result)),
+            (Output::Text, Ok(expanded)) => println!("{}", expanded.url),
+            (Output::Tsv, Ok(expanded)) => println!("{}\t{}", original, expanded.url),
+            (Output::Map, Ok(expanded)) => println!("{} -> {}", original, expanded.url),
+            (Output::Text, Err(_)) if self.only == Only::Failed => println!("{}", original),
+            (Output::Tsv, Err(e)) if self.only == Only::Failed => println!("{}\t{}", original, e),
+            (Output::Map, Err(e)) if self.only == Only::Failed => {
+                println!("{} -> error: {}", original, e)
+            }
+            (_, Err(_)) if self.verbosity == Verbosity::Quiet => {}
+            (_, Err(e)) if self.many => {
+                eprintln!("{}\t{} {}", original, self.error_label(), e)
+            }
+            (_, Err(e)) => eprintln!("{} {}", self.error_label(), e),
+        }
+    }
+
+    /// Like `print`, with every hop of the expansion in the text format
+    pub(crate) fn print_chain(&mut self, original: &str, result: &Result<ExpandedUrl>) {
+        let (Output::Text, Ok(expanded)) = (self.output, result) else {
+            return self.print(original, result);
+        };
+        if self.verbosity == Verbosity::Quiet {
+            return self.print(original, result);
+        }
+        for hop in &expanded.chain {
+            println!("{}", hop_line(hop, true, self.colors.stdout));
+        }
+        println!("→ {}", paint(self.colors.stdout, color::URL, &expanded.url));
+        self.warn(original, expanded);
+    }
+
+    fn error_label(&self) -> String {
+        paint(self.colors.stderr, color::ERROR, "error:")
+    }
+
+    /// Warn on stderr about a destination known to be gone
+    fn warn(&self, original: &str, expanded: &ExpandedUrl) {
+        if self.verbosity == Verbosity::Quiet || !expanded.is_dead() {
+            return;
+        }
+        let label = paint(self.colors.stderr, color::WARNING, "warning:");
+        let status = expanded.status.unwrap_or_default();
+        if self.many {
+            eprintln!("{}\t{} dead link ({})", original, label, status);
+        } else {
+            eprintln!("{} dead link ({})", label, status);
+        }
+    }
+
+    /// The hops of an expansion on stderr, when verbose
+    fn print_hops(&self, original: &str, expanded: &ExpandedUrl) {
+        if self.verbosity < Verbosity::Hops {
+            return;
+        }
+        let detailed = self.verbosity >= Verbosity::Timings;
+        if self.many || detailed {
+            eprintln!("{} ({})", original, expanded.service);
+        }
+        for hop in &expanded.chain {
+            eprintln!("{}", hop_line(hop, detailed, self.colors.stderr));
+        }
+    }
+
+    /// Print what was held back until the end of the run
+    pub(crate) fn finish(self) {
+        if self.output != Output::Json {
+            return;
+        }
+        let json = if self.many {
+            Value::Array(self.records)
+        } else {
+            self.records.into_iter().next().unwrap_or_default()
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json).unwrap_or_default()
+        );
+    }
+}
+
+/// A hop with its status and time, and the details of the time when `detailed`
+fn hop_line(hop: &HopInfo, detailed: bool, colored: bool) -> String {
+    let details = match hop.timing.dns {
+        Some(dns) if detailed => format!(
+            " (dns {}ms, ttfb {}ms)",
+            dns.as_millis(),
+            hop.timing.ttfb.as_millis()
+        ),
+        None if detailed => format!(" (ttfb {}ms)", hop.timing.ttfb.as_millis()),
+        _ => String::new(),
+    };
+    format!(
+        "{:>2}  {}  {:>5}ms{}  {}",
+        hop.index,
+        paint(colored, color::status(hop.status), hop.status),
+        hop.timing.total.as_millis(),
+        paint(colored && !details.is_empty(), color::DETAIL, details),
+        hop.url
+    )
+}
+
+/// The CSV row of an expansion, in the order of `CSV_HEADER`
+fn csv_row(original: &str, result: &Result<ExpandedUrl>) -> String {
+    let fields = match result {
+        Ok(expanded) => [
+            original.into(),
+            expanded.url.clone(),
+            expanded.service.clone(),
+            expanded.status.map(|s| s.to_string()).unwrap_or_default(),
+            String::new(),
+        ],
+        Err(e) => [
+            original.into(),
+            String::new(),
+            String::new(),
+            String::new(),
+            e.to_string(),
+        ],
+    };
+    fields
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The JSON record of an expansion
+pub(crate) fn record(original: &str, result: &Result<ExpandedUrl>) -> Value {
+    match result {
+        Ok(expanded) => json!({
+            "original": original,
+            "url": expanded.url,
+            "service": expanded.service,
+            "status": expanded.status,
+            "chain": expanded.chain.iter().map(|hop| json!({
+                "url": hop.url,
+                "status": hop.status,
+                "ms": hop.timing.total.as_millis() as u64,
+            })).collect::<Vec<_>>(),
+            "error": null,
+        }),
+        Err(e) => json!({
+            "original": original,
+            "url": null,
+            "service": null,
+            "status": null,
+            "chain": [],
+            "error": e.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use urlexpand::Error;
+
+    #[test]
+    fn test_csv_row() {
+        let failed = Err(Error::Connect("refused, twice".into()));
+        assert_eq!(
+            csv_row("https://bit.ly/a,b", &failed),
+            "\"https://bit.ly/a,b\",,,,\"connection failed: refused, twice\""
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let failed = record("https://bit.ly/abc", &Err(Error::NoString));
+        assert_eq!(failed["original"], "https://bit.ly/abc");
+        assert!(failed["url"].is_null());
+        assert_eq!(failed["chain"], json!([]));
+        assert_eq!(failed["error"], Error::NoString.to_string());
+    }
+}
@@ -0,0 +1,155 @@
+// Per-client rate limit of the HTTP API
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Clients tracked before the idle ones, then the least recently seen, are
+/// forgotten
+static MAX_CLIENTS: usize = 10_000;
+
+/// Requests a client may still make, and when they were counted
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Why requests were refused
+#[derive(Debug, PartialEq)]
+pub(crate) enum Limited {
+    /// The client has to wait this long to make them
+    Wait(Duration),
+    /// More than the client may ever make at once: they never pass
+    TooMany,
+}
+
+/// A token bucket per client address (per /64 network for IPv6, which hands
+/// one to every host): `per_minute` requests, in bursts of up to
+/// `per_minute`, refilled continuously
+pub(crate) struct RateLimiter {
+    per_minute: f64,
+    clients: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute: f64::from(per_minute.max(1)),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count `cost` requests of the client, or tell how long it has to wait
+    /// to make them
+    pub(crate) fn take(&self, client: IpAddr, cost: usize) -> Result<(), Limited> {
+        let cost = cost as f64;
+        if cost > self.per_minute {
+            return Err(Limited::TooMany);
+        }
+        let now = Instant::now();
+        let per_second = self.per_minute / 60.0;
+        let client = network(client);
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if clients.len() >= MAX_CLIENTS && !clients.contains_key(&client) {
+            // a full bucket is the same as a forgotten one
+            clients.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * per_second
+                    < self.per_minute
+            });
+            if clients.len() >= MAX_CLIENTS {
+                let oldest = clients
+                    .iter()
+                    .min_by_key(|(_, bucket)| bucket.updated)
+                    .map(|(client, _)| *client);
+                if let Some(oldest) = oldest {
+                    clients.remove(&oldest);
+                }
+            }
+        }
+        let bucket = clients.entry(client).or_insert(Bucket {
+            tokens: self.per_minute,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(self.per_minute);
+        bucket.updated = now;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Limited::Wait(Duration::from_secs_f64(
+                (cost - bucket.tokens) / per_second,
+            )))
+        }
+    }
+}
+
+/// The address a client is counted under: its /64 network for IPv6
+fn network(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => v4.into(),
+            None => Ipv6Addr::from(u128::from(v6) & !(u64::MAX as u128)).into(),
+        },
+        v4 => v4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let limiter = RateLimiter::new(60);
+        let (client, other) = ([127, 0, 0, 1].into(), [127, 0, 0, 2].into());
+        assert_eq!(limiter.take(client, 50), Ok(()));
+        assert_eq!(limiter.take(client, 10), Ok(()));
+        // a token a second comes back
+        match limiter.take(client, 2) {
+            Err(Limited::Wait(wait)) => {
+                assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2))
+            }
+            refused => panic!("{:?}", refused),
+        }
+        assert_eq!(limiter.take(other, 60), Ok(()));
+    }
+
+    #[test]
+    fn test_take_too_many() {
+        let limiter = RateLimiter::new(10);
+        let client = [127, 0, 0, 1].into();
+        assert_eq!(limiter.take(client, 11), Err(Limited::TooMany));
+        // refusing them took nothing
+        assert_eq!(limiter.take(client, 10), Ok(()));
+    }
+
+    #[test]
+    fn test_take_ipv6_network() {
+        let limiter = RateLimiter::new(10);
+        let client: IpAddr = "2001:db8::1".parse().unwrap();
+        assert_eq!(limiter.take(client, 10), Ok(()));
+        // another host of the same /64
+        let neighbour = "2001:db8::ffff:1".parse().unwrap();
+        assert!(matches!(limiter.take(neighbour, 1), Err(Limited::Wait(_))));
+        let other = "2001:db8:0:1::1".parse().unwrap();
+        assert_eq!(limiter.take(other, 10), Ok(()));
+    }
+
+    #[test]
+    fn test_take_bounded() {
+        let limiter = RateLimiter::new(10);
+        let client = |i: usize| IpAddr::from([10, 0, (i >> 8) as u8, i as u8]);
+        // busy clients, none of which can be forgotten as idle
+        for i in 0..MAX_CLIENTS {
+            assert_eq!(limiter.take(client(i), 10), Ok(()));
+        }
+        assert_eq!(limiter.take([192, 0, 2, 1].into(), 10), Ok(()));
+        assert_eq!(limiter.clients.lock().unwrap().len(), MAX_CLIENTS);
+        // the least recently seen client made room, and starts over when back
+        assert_eq!(limiter.take(client(0), 10), Ok(()));
+        assert!(matches!(limiter.take(client(2), 1), Err(Limited::Wait(_))));
+    }
+}
@@ -0,0 +1,85 @@
+// Interactive prompt, with line editing and a history kept between sessions
+use std::{env, fs, path::PathBuf};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+use tokio::runtime::Runtime;
+use urlexpand::{is_shortened, Error, Expander};
+
+/// `$XDG_DATA_HOME/urlexpand/history`, or `~/.local/share/urlexpand/history`
+fn history_path() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+    Some(data_home.join("urlexpand").join("history"))
+}
+
+/// Read `check` / `expand` commands until `quit`, Ctrl-D or the end of the
+/// input; the arrows browse the history and Ctrl-R searches it
+pub(crate) fn run(expander: &Expander, rt: &Runtime) -> urlexpand::Result<()> {
+    let mut editor = DefaultEditor::new().map_err(|e| Error::StdIo(e.to_string()))?;
+    let history = history_path();
+    if let Some(path) = &history {
+        // no history yet on the first session
+        editor.load_history(path).ok();
+    }
+    println!("URL Expander (type 'help' for commands)\n");
+
+    loop {
+        let input = match editor.readline("> ") {
+            Ok(input) => input,
+            // Ctrl-C drops the line being typed
+            Err(ReadlineError::Interrupted) => continue,
+            Err(_) => break,
+        };
+        if !input.trim().is_empty() {
+            editor.add_history_entry(input.as_str()).ok();
+        }
+
+        let parts: Vec<&str> = input.trim().splitn(2, ' ').collect();
+        let cmd = parts.first().copied().unwrap_or("");
+        let url = parts.get(1).copied().unwrap_or("");
+
+        match cmd {
+            "check" | "c" => {
+                if url.is_empty() {
+                    println!("usage: check <url>");
+                } else if is_shortened(url) {
+                    println!("✓ shortened");
+                } else {
+                    println!("✗ not shortened");
+                }
+            }
+            "expand" | "e" => {
+                if url.is_empty() {
+                    println!("usage: expand <url>");
+                } else if !is_shortened(url) {
+                    println!("✗ not a shortened url");
+                } else {
+                    match rt.block_on(expander.expand(url)) {
+                        Ok(expanded) => println!("→ {}", expanded.url),
+                        Err(e) => println!("✗ {}", e),
+                    }
+                }
+            }
+            "help" | "h" => {
+                println!("check <url>  - check if url is shortened");
+                println!("expand <url> - expand shortened url");
+                println!("quit         - exit");
+            }
+            "quit" | "q" | "exit" => break,
+            "" => {}
+            _ => println!("unknown command (try 'help')"),
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        editor
+            .save_history(path)
+            .map_err(|e| Error::StdIo(e.to_string()))?;
+    }
+    Ok(())
+}
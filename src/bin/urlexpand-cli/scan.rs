@@ -0,0 +1,165 @@
+// Shortened urls of text, Markdown and HTML files, reported or rewritten
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anstyle::AnsiColor;
+use futures::{stream, StreamExt};
+use urlexpand::{Expander, Result};
+
+use crate::{
+    color::{paint, Colors},
+    exit::{Failures, Status},
+    links,
+};
+
+/// Extensions of the files scanned in directories
+static EXTENSIONS: [&str; 5] = ["txt", "md", "markdown", "html", "htm"];
+
+/// The files to scan: `path` itself, or the text, Markdown and HTML files
+/// under it, hidden directories aside
+pub(crate) fn files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.into()]);
+    }
+    let mut files = vec![];
+    let mut entries: Vec<_> = fs::read_dir(path)?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(self::files(&path)?);
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// A shortened url of a file
+struct Found {
+    /// Line of the url, starting at 1
+    line: usize,
+    url: String,
+}
+
+/// The shortened urls of a text, line by line
+fn find(expander: &Expander, text: &str) -> Vec<Found> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            links::find_shortened(expander, line)
+                .into_iter()
+                .map(move |range| Found {
+                    line: index + 1,
+                    url: line[range].into(),
+                })
+        })
+        .collect()
+}
+
+/// Print `file:line: url` for every shortened url of the files
+pub(crate) fn report(expander: &Expander, files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        for found in find(expander, &read(file)?) {
+            println!("{}:{}: {}", file.display(), found.line, found.url);
+        }
+    }
+    Ok(())
+}
+
+/// Replace the shortened urls of the files by their expansion, printing
+/// the changed lines as a diff; the urls that cannot be expanded are kept
+pub(crate) async fn rewrite(
+    expander: &Expander,
+    files: &[PathBuf],
+    concurrency: usize,
+    colors: Colors,
+) -> Result<Failures> {
+    let mut texts = Vec::with_capacity(files.len());
+    let mut urls: Vec<String> = vec![];
+    for file in files {
+        let text = read(file)?;
+        urls.extend(find(expander, &text).into_iter().map(|found| found.url));
+        texts.push(text);
+    }
+    urls.sort_unstable();
+    urls.dedup();
+
+    let mut failures = Failures::default();
+    let mut expansions = HashMap::new();
+    let mut results = stream::iter(urls)
+        .map(|url| async move {
+            let result = expander.expand(&url).await;
+            (url, result)
+        })
+        .buffer_unordered(concurrency);
+    while let Some((url, result)) = results.next().await {
+        match result {
+            Ok(expanded) => {
+                expansions.insert(url, expanded.url);
+            }
+            Err(e) => {
+                eprintln!("{}\terror: {}", url, e);
+                failures.add(Status::of(expander, &url, &e));
+            }
+        }
+    }
+
+    for (file, text) in files.iter().zip(texts) {
+        let mut changes = vec![];
+        let lines: Vec<String> = text
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(index, line)| {
+                let mut rewritten = line.to_string();
+                // from the end of the line, so that the ranges before stay valid
+                let mut ranges = links::find_shortened(expander, line);
+                ranges.sort_by_key(|range| std::cmp::Reverse(range.start));
+                for range in ranges {
+                    if let Some(expanded) = expansions.get(&line[range.clone()]) {
+                        rewritten.replace_range(range, expanded);
+                    }
+                }
+                if rewritten != line {
+                    changes.push((index + 1, line.to_string(), rewritten.clone()));
+                }
+                rewritten
+            })
+            .collect();
+        if changes.is_empty() {
+            continue;
+        }
+        fs::write(file, lines.concat())?;
+        print_diff(file, &changes, colors);
+    }
+    Ok(failures)
+}
+
+fn read(file: &Path) -> Result<String> {
+    fs::read_to_string(file)
+        .map_err(|e| urlexpand::Error::StdIo(format!("{}: {}", file.display(), e)))
+}
+
+/// The changed lines of a file, as a unified diff without context
+fn print_diff(file: &Path, changes: &[(usize, String, String)], colors: Colors) {
+    println!("--- {}", file.display());
+    println!("+++ {}", file.display());
+    for (line, old, new) in changes {
+        println!("@@ -{line},1 +{line},1 @@");
+        let (old, new) = (old.trim_end_matches('\n'), new.trim_end_matches('\n'));
+        let removed = AnsiColor::Red.on_default();
+        let added = AnsiColor::Green.on_default();
+        println!("{}", paint(colors.stdout, removed, format!("-{}", old)));
+        println!("{}", paint(colors.stdout, added, format!("+{}", new)));
+    }
+}
@@ -0,0 +1,277 @@
+// HTTP API over a shared expander
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use futures::{stream, StreamExt};
+use hyper::{
+    body::HttpBody,
+    header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE},
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use serde_json::{json, Value};
+use urlexpand::{Error, Expander, Result};
+
+use crate::{
+    exit::Status,
+    output,
+    ratelimit::{Limited, RateLimiter},
+};
+
+/// Largest body of a `POST /expand`
+static MAX_BODY: usize = 1024 * 1024;
+
+/// Who may use the API, and how much
+pub(crate) struct Access {
+    /// The token of the `Authorization: Bearer` header every request needs
+    pub(crate) token: Option<String>,
+    /// Requests (or urls of batches) a client address may send per minute
+    pub(crate) rate_limit: Option<u32>,
+}
+
+/// What the handlers share
+#[derive(Clone)]
+struct Api {
+    expander: Expander,
+    /// How many urls of a batch are expanded at the same time
+    concurrency: usize,
+    /// The metrics of the expander and of the API
+    metrics: PrometheusHandle,
+    token: Option<Arc<str>>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+/// Serve the API on `listen` until the process is stopped
+pub(crate) async fn run(
+    expander: Expander,
+    concurrency: usize,
+    access: Access,
+    listen: SocketAddr,
+) -> Result<()> {
+    let metrics = PrometheusBuilder::new()
+        .install_recorder()
+        .map_err(|e| Error::Config(format!("metrics: {}", e)))?;
+    let api = Api {
+        expander,
+        concurrency,
+        metrics,
+        token: access.token.map(Arc::from),
+        limiter: access
+            .rate_limit
+            .map(|limit| Arc::new(RateLimiter::new(limit))),
+    };
+    let make_service = make_service_fn(move |connection: &AddrStream| {
+        let (api, client) = (api.clone(), connection.remote_addr().ip());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let api = api.clone();
+                async move { Ok::<_, Infallible>(api.handle(client, request).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&listen)
+        .map_err(|e| Error::StdIo(format!("{}: {}", listen, e)))?
+        .serve(make_service);
+    eprintln!("listening on http://{}", server.local_addr());
+    server.await.map_err(|e| Error::StdIo(e.to_string()))
+}
+
+impl Api {
+    async fn handle(&self, client: IpAddr, request: Request<Body>) -> Response<Body> {
+        let path = request.uri().path().to_string();
+        // batches count their urls, once they are read
+        let batch = request.method() == Method::POST && path == "/expand";
+        let rejection = if self.authorized(&request) {
+            (!batch).then(|| self.limit(client, 1)).flatten()
+        } else {
+            let mut response = error(StatusCode::UNAUTHORIZED, "missing or invalid token");
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            Some(response)
+        };
+        let response = match (rejection, request.method(), path.as_str()) {
+            (Some(response), _, _) => response,
+            (_, &Method::GET, "/expand") => self.expand_query(request.uri().query()).await,
+            (_, &Method::POST, "/expand") => self.expand_batch(client, request.into_body()).await,
+            (_, _, "/expand") => error(StatusCode::METHOD_NOT_ALLOWED, "use GET or POST"),
+            (_, &Method::GET, "/metrics") => {
+                let mut response = Response::new(Body::from(self.metrics.render()));
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("text/plain; version=0.0.4"),
+                );
+                response
+            }
+            _ => error(StatusCode::NOT_FOUND, "not found"),
+        };
+        // unknown paths are counted together, not to grow a label per path
+        let path = match path.as_str() {
+            "/expand" | "/metrics" => path,
+            _ => "other".into(),
+        };
+        metrics::counter!(
+            "urlexpand_http_requests_total",
+            "path" => path,
+            "status" => response.status().as_u16().to_string()
+        )
+        .increment(1);
+        response
+    }
+
+    /// Whether the request has the token, when one is needed
+    fn authorized(&self, request: &Request<Body>) -> bool {
+        self.token
+            .as_ref()
+            .is_none_or(|token| has_token(request, token))
+    }
+
+    /// A `429 Too Many Requests` when the client is over its rate limit, or
+    /// a `413 Payload Too Large` when `cost` is more than it will ever allow
+    fn limit(&self, client: IpAddr, cost: usize) -> Option<Response<Body>> {
+        let wait = match self.limiter.as_ref()?.take(client, cost).err()? {
+            Limited::Wait(wait) => wait,
+            Limited::TooMany => {
+                return Some(error(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "more urls than the rate limit allows per minute",
+                ))
+            }
+        };
+        let mut response = error(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(wait.as_secs().max(1)));
+        Some(response)
+    }
+
+    /// `GET /expand?url=...`: the record of the expansion, with a status
+    /// telling how it went
+    async fn expand_query(&self, query: Option<&str>) -> Response<Body> {
+        let url = url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+            .find(|(name, _)| name == "url")
+            .map(|(_, url)| url.into_owned());
+        let Some(url) = url else {
+            return error(StatusCode::BAD_REQUEST, "missing url parameter");
+        };
+        let result = self.expander.expand(&url).await;
+        let status = match &result {
+            Ok(_) => StatusCode::OK,
+            Err(e) => match Status::of(&self.expander, &url, e) {
+                Status::InvalidInput => StatusCode::UNPROCESSABLE_ENTITY,
+                Status::Network if matches!(e, Error::Timeout { .. }) => {
+                    StatusCode::GATEWAY_TIMEOUT
+                }
+                _ => StatusCode::BAD_GATEWAY,
+            },
+        };
+        respond(status, &output::record(&url, &result))
+    }
+
+    /// `POST /expand` with a JSON array of urls (or `{"urls": [...]}`): the
+    /// records of their expansions, in the same order
+    async fn expand_batch(&self, client: IpAddr, body: Body) -> Response<Body> {
+        let Some(body) = read_body(body).await else {
+            return error(StatusCode::PAYLOAD_TOO_LARGE, "body too large");
+        };
+        let urls = match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Array(urls)) => urls,
+            Ok(Value::Object(mut object)) => match object.remove("urls") {
+                Some(Value::Array(urls)) => urls,
+                _ => return error(StatusCode::BAD_REQUEST, "expected an array of urls"),
+            },
+            _ => return error(StatusCode::BAD_REQUEST, "expected an array of urls"),
+        };
+        let Some(urls) = urls
+            .into_iter()
+            .map(|url| url.as_str().map(String::from))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return error(StatusCode::BAD_REQUEST, "expected an array of urls");
+        };
+        // an empty batch is a request like any other
+        if let Some(response) = self.limit(client, urls.len().max(1)) {
+            return response;
+        }
+        let records: Vec<Value> = stream::iter(urls)
+            .map(|url| async move { output::record(&url, &self.expander.expand(&url).await) })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+        respond(StatusCode::OK, &Value::Array(records))
+    }
+}
+
+/// Whether the `Authorization: Bearer` header of the request has the token
+fn has_token(request: &Request<Body>, token: &str) -> bool {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| same_secret(given.trim(), token))
+}
+
+/// Compare secrets in a time that does not tell how much of them matched
+fn same_secret(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// The body of a request, unless larger than `MAX_BODY`
+async fn read_body(mut body: Body) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > MAX_BODY {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+fn respond(status: StatusCode, json: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(json.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    respond(status, &json!({ "error": message }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder().uri("/expand");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_has_token() {
+        assert!(has_token(&request(Some("Bearer s3cret")), "s3cret"));
+        assert!(has_token(&request(Some("Bearer  s3cret ")), "s3cret"));
+        assert!(!has_token(&request(Some("Bearer s3cre")), "s3cret"));
+        assert!(!has_token(&request(Some("Bearer s3creT")), "s3cret"));
+        assert!(!has_token(&request(Some("Basic s3cret")), "s3cret"));
+        assert!(!has_token(&request(Some("s3cret")), "s3cret"));
+        assert!(!has_token(&request(None), "s3cret"));
+    }
+}
@@ -0,0 +1,94 @@
+// Per-service circuit breaker
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{Error, Result};
+
+/// Consecutive failures of a service, when its circuit was opened, and
+/// whether an expansion is probing it
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    opened: Option<Instant>,
+    probing: bool,
+}
+
+/// Fails fast for services that failed `threshold` times in a row, until
+/// `cooldown` has passed; a single expansion is then let through to probe
+/// the service, a failure opening the circuit again and a success closing
+/// it, while the others keep failing fast
+pub(crate) struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    services: Mutex<HashMap<String, Health>>,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            services: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `Error::ServiceUnavailable` while the circuit of the service is open,
+    /// or while it is being probed; the probe of a cooled down circuit
+    pub(crate) fn check(&self, service: &str) -> Result<Option<Probe<'_>>> {
+        let mut services = self.services.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(health) = services.get_mut(service) else {
+            return Ok(None);
+        };
+        match health.opened {
+            None => Ok(None),
+            Some(opened) if opened.elapsed() < self.cooldown || health.probing => {
+                Err(Error::ServiceUnavailable(service.into()))
+            }
+            Some(_) => {
+                health.probing = true;
+                Ok(Some(Probe {
+                    breaker: self,
+                    service: service.into(),
+                }))
+            }
+        }
+    }
+
+    /// Count the outcome of an expansion through the service
+    pub(crate) fn record(&self, service: &str, success: bool) {
+        let mut services = self.services.lock().unwrap_or_else(PoisonError::into_inner);
+        if success {
+            services.remove(service);
+            return;
+        }
+        let health = services.entry(service.into()).or_default();
+        health.failures = health.failures.saturating_add(1);
+        if health.failures >= self.threshold {
+            health.opened = Some(Instant::now());
+        }
+    }
+}
+
+/// The expansion let through a cooled down circuit, to be dropped once its
+/// outcome is recorded; a probe dropped before (a cancelled expansion) lets
+/// the next expansion probe the service instead
+pub(crate) struct Probe<'a> {
+    breaker: &'a CircuitBreaker,
+    service: String,
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        let mut services = self
+            .breaker
+            .services
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(health) = services.get_mut(&self.service) {
+            health.probing = false;
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// In-memory cache of expansions
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::ExpandedUrl;
+
+/// Make room for one more entry in a map holding at most `capacity`, by
+/// dropping its oldest entries, as dated by `at`
+pub(crate) fn evict_oldest<V>(
+    entries: &mut HashMap<String, V>,
+    capacity: usize,
+    at: impl Fn(&V) -> Instant,
+) {
+    while !entries.is_empty() && entries.len() >= capacity {
+        let oldest = entries
+            .iter()
+            .min_by_key(|(_, value)| at(value))
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            entries.remove(&oldest);
+        }
+    }
+}
+
+/// Expansions by short url, kept for a while and up to a number of entries
+pub(crate) struct ExpansionCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, ExpandedUrl)>>,
+}
+
+impl ExpansionCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached expansion of a url, unless it expired
+    pub(crate) fn get(&self, url: &str) -> Option<ExpandedUrl> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(url)
+            .filter(|(stored, _)| stored.elapsed() < self.ttl)
+            .map(|(_, expanded)| expanded.clone())
+    }
+
+    /// The cached expansion of a url that expired, to be revalidated
+    pub(crate) fn get_expired(&self, url: &str) -> Option<ExpandedUrl> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(url)
+            .filter(|(stored, _)| stored.elapsed() >= self.ttl)
+            .map(|(_, expanded)| expanded.clone())
+    }
+
+    /// Cache an expansion, making room by dropping the expired entries
+    /// first and the oldest ones after that
+    pub(crate) fn insert(&self, url: &str, expanded: &ExpandedUrl) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.capacity && !entries.contains_key(url) {
+            entries.retain(|_, (stored, _)| stored.elapsed() < self.ttl);
+            evict_oldest(&mut entries, self.capacity, |(stored, _)| *stored);
+        }
+        entries.insert(url.into(), (Instant::now(), expanded.clone()));
+    }
+}
@@ -0,0 +1,270 @@
+// Expander configuration from outside of the code
+use std::{
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+
+#[cfg(feature = "config-file")]
+use std::collections::HashMap;
+
+use crate::{Error, Expander, ExpanderBuilder, Result};
+
+/// Prefix of the environment variables read by [`Config::from_env`]
+static ENV_PREFIX: &str = "URLEXPAND_";
+
+/// Configuration of the expanders behind the free functions
+static DEFAULT_CONFIG: RwLock<Config> = RwLock::new(Config {
+    timeout: None,
+    proxy: None,
+    user_agent: None,
+    max_redirects: None,
+});
+
+/// Defaults for an [`Expander`](crate::Expander), applied with
+/// [`ExpanderBuilder::config`](crate::ExpanderBuilder::config); unset
+/// values keep the builder defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Timeout of every request
+    pub timeout: Option<Duration>,
+    /// Proxy url every request goes through
+    pub proxy: Option<String>,
+    /// `User-Agent` sent to the shorteners
+    pub user_agent: Option<String>,
+    /// Redirects followed before giving up
+    pub max_redirects: Option<usize>,
+}
+
+impl Config {
+    /// Read the configuration from the environment:
+    ///
+    /// - `URLEXPAND_TIMEOUT`: timeout in seconds (`10`, `2.5`)
+    /// - `URLEXPAND_PROXY`: proxy url
+    /// - `URLEXPAND_USER_AGENT`: user agent
+    /// - `URLEXPAND_MAX_REDIRECTS`: number of redirects
+    ///
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::{Config, Expander};
+    ///
+    /// let config = Config::from_env()?;
+    /// let expander = Expander::builder().config(&config).build()?;
+    /// # Ok::<(), urlexpand::Error>(())
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Read the configuration from `URLEXPAND_*` variables, ignoring the others
+    pub(crate) fn from_vars<I: IntoIterator<Item = (String, String)>>(vars: I) -> Result<Self> {
+        let mut config = Self::default();
+        for (name, value) in vars {
+            let Some(name) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let value = value.trim();
+            match name {
+                "TIMEOUT" => {
+                    let secs = value
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(|| invalid(name, value))?;
+                    config.timeout = Some(secs);
+                }
+                "PROXY" => config.proxy = Some(value.into()),
+                "USER_AGENT" => config.user_agent = Some(value.into()),
+                "MAX_REDIRECTS" => {
+                    config.max_redirects = Some(value.parse().map_err(|_| invalid(name, value))?)
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Overrides of a single call to [`unshorten_with`](crate::unshorten_with),
+/// on top of the configuration set with [`set_default_config`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Timeout of every request
+    pub timeout: Option<Duration>,
+    /// Redirects followed before giving up
+    pub max_redirects: Option<usize>,
+    /// `User-Agent` sent to the shorteners
+    pub user_agent: Option<String>,
+    /// Proxy url every request goes through
+    pub proxy: Option<String>,
+    /// Schemes a redirect or the expanded url may use (default: http & https)
+    pub allowed_schemes: Option<Vec<String>>,
+    /// Fail with `Error::Downgrade` on redirects from https to plain http
+    pub refuse_downgrades: bool,
+    /// Retry urls given without a scheme over plain http when https fails
+    /// to connect
+    pub http_fallback: bool,
+    /// Refuse to connect to private addresses, see
+    /// [`ExpanderBuilder::block_private_addresses`]
+    pub block_private_addresses: bool,
+}
+
+impl Options {
+    /// The builder of the expander of a call: the default configuration,
+    /// overridden by these options
+    pub(crate) fn builder(&self) -> ExpanderBuilder {
+        let mut builder = Expander::builder()
+            .config(&default_config())
+            .config(&Config {
+                timeout: self.timeout,
+                proxy: self.proxy.clone(),
+                user_agent: self.user_agent.clone(),
+                max_redirects: self.max_redirects,
+            })
+            .refuse_downgrades(self.refuse_downgrades)
+            .http_fallback(self.http_fallback)
+            .block_private_addresses(self.block_private_addresses);
+        if let Some(schemes) = &self.allowed_schemes {
+            builder = builder.allowed_schemes(schemes);
+        }
+        builder
+    }
+}
+
+pub fn set_default_config(config: Config) {
+    //! Configure the free functions ([`unshorten`](crate::unshorten) and
+    //! friends) for the whole process; a timeout given to them still wins
+    //! ## Example
+    //! ```rust
+    //! use std::time::Duration;
+    //! use urlexpand::{set_default_config, Config};
+    //!
+    //! set_default_config(Config {
+    //!     timeout: Some(Duration::from_secs(5)),
+    //!     user_agent: Some("my-crawler/1.0".into()),
+    //!     max_redirects: Some(5),
+    //!     ..Default::default()
+    //! });
+    //! ```
+    *DEFAULT_CONFIG
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = config;
+}
+
+/// The configuration set with [`set_default_config`]
+pub(crate) fn default_config() -> Config {
+    DEFAULT_CONFIG
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+fn invalid(name: &str, value: &str) -> Error {
+    Error::Config(format!("{}{}={}", ENV_PREFIX, name, value))
+}
+
+/// Configuration file shared by the library and `urlexpand-cli`
+///
+/// ```toml
+/// timeout = 10            # seconds
+/// connect_timeout = 2.5
+/// proxy = "socks5://127.0.0.1:9050"
+/// user_agent = "my-crawler/1.0"
+/// max_redirects = 5
+///
+/// [services]
+/// allow = []              # only expand these services (empty: all of them)
+/// deny = ["adf.ly"]       # never expand these services
+/// custom = ["go.corp.example"]  # more shorteners, followed like plain redirects
+///
+/// [timeouts]             # seconds, by service
+/// "adf.ly" = 30
+/// "bit.ly" = 2
+///
+/// [cache]
+/// capacity = 10000        # expansions kept in memory
+/// ttl = 3600              # seconds
+/// ```
+#[cfg(feature = "config-file")]
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct ExpanderConfig {
+    /// Timeout of every request, in seconds
+    pub timeout: Option<f64>,
+    /// Timeout of the connection phase of every request, in seconds
+    pub connect_timeout: Option<f64>,
+    /// Proxy url every request goes through
+    pub proxy: Option<String>,
+    /// `User-Agent` sent to the shorteners
+    pub user_agent: Option<String>,
+    /// Redirects followed before giving up
+    pub max_redirects: Option<usize>,
+    pub services: ServicesConfig,
+    /// Timeouts of the requests of some services, in seconds
+    pub timeouts: HashMap<String, f64>,
+    /// Cache of the expansions, disabled when missing
+    pub cache: Option<CacheConfig>,
+}
+
+/// Which shortener services may be expanded
+#[cfg(feature = "config-file")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct ServicesConfig {
+    /// Only expand urls of these services; every service when empty
+    pub allow: Vec<String>,
+    /// Never expand urls of these services
+    pub deny: Vec<String>,
+    /// Domains to handle as shorteners on top of the known ones
+    pub custom: Vec<String>,
+}
+
+/// Size and lifetime of the expansion cache
+#[cfg(feature = "config-file")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Maximum number of cached expansions
+    pub capacity: usize,
+    /// Seconds an expansion stays cached
+    pub ttl: u64,
+}
+
+#[cfg(feature = "config-file")]
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            ttl: 3600,
+        }
+    }
+}
+
+#[cfg(feature = "config-file")]
+impl ExpanderConfig {
+    /// Read the configuration from a TOML file
+    pub fn from_toml<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let toml = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        Self::from_toml_str(&toml)
+    }
+
+    /// Read the configuration from TOML text
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::{Expander, ExpanderConfig};
+    ///
+    /// let config = ExpanderConfig::from_toml_str("timeout = 5\n[services]\ndeny = [\"adf.ly\"]")?;
+    /// let expander = Expander::builder().expander_config(&config)?.build()?;
+    /// # Ok::<(), urlexpand::Error>(())
+    /// ```
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| Error::Config(e.to_string()))
+    }
+}
+
+/// Seconds of a configuration file as a `Duration`
+#[cfg(feature = "config-file")]
+pub(crate) fn seconds(name: &str, secs: f64) -> Result<Duration> {
+    Duration::try_from_secs_f64(secs).map_err(|_| Error::Config(format!("{} = {}", name, secs)))
+}
@@ -0,0 +1,150 @@
+// Offline decoders for link wrappers that carry their destination in the url
+use base64::{engine::general_purpose, Engine as _};
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::parse_input;
+
+/// Characters of the length marker of urldefense v3 replacement runs
+static URLDEFENSE_RUN_LENGTHS: &str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Public suffixes of the Google search domains (`google.<suffix>`), sorted
+static GOOGLE_SUFFIXES: &[&str] = &[
+    "ad", "ae", "al", "am", "as", "at", "az", "ba", "be", "bf", "bg", "bi", "bj", "bs", "bt", "by",
+    "ca", "cat", "cd", "cf", "cg", "ch", "ci", "cl", "cm", "cn", "co.ao", "co.bw", "co.ck",
+    "co.cr", "co.id", "co.il", "co.in", "co.jp", "co.ke", "co.kr", "co.ls", "co.ma", "co.mz",
+    "co.nz", "co.th", "co.tz", "co.ug", "co.uk", "co.uz", "co.ve", "co.vi", "co.za", "co.zm",
+    "co.zw", "com", "com.af", "com.ag", "com.ar", "com.au", "com.bd", "com.bh", "com.bn", "com.bo",
+    "com.br", "com.bz", "com.co", "com.cu", "com.cy", "com.do", "com.ec", "com.eg", "com.et",
+    "com.fj", "com.gh", "com.gi", "com.gt", "com.hk", "com.jm", "com.kh", "com.kw", "com.lb",
+    "com.ly", "com.mm", "com.mt", "com.mx", "com.my", "com.na", "com.ng", "com.ni", "com.np",
+    "com.om", "com.pa", "com.pe", "com.pg", "com.ph", "com.pk", "com.pr", "com.py", "com.qa",
+    "com.sa", "com.sb", "com.sg", "com.sl", "com.sv", "com.tj", "com.tr", "com.tw", "com.ua",
+    "com.uy", "com.vc", "com.vn", "cv", "cz", "de", "dj", "dk", "dm", "dz", "ee", "es", "fi", "fm",
+    "fr", "ga", "ge", "gg", "gl", "gm", "gr", "gy", "hn", "hr", "ht", "hu", "ie", "im", "iq", "is",
+    "it", "je", "jo", "kg", "ki", "kz", "la", "li", "lk", "lt", "lu", "lv", "md", "me", "mg", "mk",
+    "ml", "mn", "mu", "mv", "mw", "ne", "nl", "no", "nr", "nu", "pl", "pn", "ps", "pt", "ro", "rs",
+    "ru", "rw", "sc", "se", "sh", "si", "sk", "sm", "sn", "so", "sr", "st", "td", "tg", "tl", "tm",
+    "tn", "to", "tt", "vu", "ws",
+];
+
+/// The destination of a wrapped link, and the name of its wrapper, without
+/// any request
+pub(crate) fn decode(url: &str) -> Option<(&'static str, String)> {
+    let url = parse_input(url)?;
+    let host = url.host_str()?.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    let (wrapper, decoded) = if host.ends_with("safelinks.protection.outlook.com") {
+        ("safelinks.protection.outlook.com", safelinks(&url))
+    } else if host == "urldefense.proofpoint.com" || host == "urldefense.com" {
+        ("urldefense.com", urldefense(&url))
+    } else if is_google(host) && url.path() == "/url" {
+        ("google.com", query_param(&url, &["q", "url"]))
+    } else if host == "l.facebook.com" || host == "lm.facebook.com" {
+        ("l.facebook.com", query_param(&url, &["u"]))
+    } else if host == "wa.me" {
+        ("wa.me", whatsapp(&url))
+    } else if host == "youtu.be" {
+        ("youtu.be", youtube(&url))
+    } else {
+        return None;
+    };
+    decoded
+        .filter(|decoded| Url::parse(decoded).is_ok())
+        .map(|decoded| (wrapper, decoded))
+}
+
+/// `google.com`, `google.co.uk`, `google.de`...
+fn is_google(host: &str) -> bool {
+    host.strip_prefix("google.")
+        .is_some_and(|suffix| GOOGLE_SUFFIXES.binary_search(&suffix).is_ok())
+}
+
+fn query_param(url: &Url, names: &[&str]) -> Option<String> {
+    url.query_pairs()
+        .find(|(name, _)| names.contains(&name.as_ref()))
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Microsoft Defender SafeLinks: `https://eur01.safelinks.protection.outlook.com/?url=...`
+pub(crate) fn safelinks(url: &Url) -> Option<String> {
+    query_param(url, &["url"])
+}
+
+/// Proofpoint URL Defense, versions 1 to 3
+fn urldefense(url: &Url) -> Option<String> {
+    let path = url.path();
+    if path.starts_with("/v1/") {
+        query_param(url, &["u"])
+    } else if path.starts_with("/v2/") {
+        // percent-encoding with `-` for `%` and `_` for `/`
+        let encoded = query_param(url, &["u"])?
+            .replace('-', "%")
+            .replace('_', "/");
+        Some(percent_decode_str(&encoded).decode_utf8_lossy().into())
+    } else if path.starts_with("/v3/") {
+        urldefense_v3(url.as_str())
+    } else {
+        None
+    }
+}
+
+/// `https://urldefense.com/v3/__<url with * placeholders>__;<base64 of the replaced characters>!!...`
+fn urldefense_v3(url: &str) -> Option<String> {
+    let wrapped = url.split_once("/v3/__")?.1;
+    let (target, rest) = wrapped.split_once("__;")?;
+    let encoded = rest.split('!').next().unwrap_or_default();
+    let replaced = general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded.trim_end_matches('='))
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_default();
+    let mut replaced = replaced.chars();
+    let mut decoded = String::with_capacity(target.len());
+    let mut chars = target.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '*' {
+            decoded.push(ch);
+            continue;
+        }
+        // `*` stands for one character, `**X` for a run of them
+        let run = if chars.as_str().starts_with('*') {
+            chars.next();
+            URLDEFENSE_RUN_LENGTHS.find(chars.next()?)? + 2
+        } else {
+            1
+        };
+        for _ in 0..run {
+            decoded.push(replaced.next()?);
+        }
+    }
+    Some(decoded)
+}
+
+/// `https://wa.me/15551234567?text=hi` -> the WhatsApp chat it opens
+fn whatsapp(url: &Url) -> Option<String> {
+    let phone = url.path().trim_matches('/');
+    if phone.is_empty() || !phone.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut chat = Url::parse("https://api.whatsapp.com/send").ok()?;
+    chat.query_pairs_mut()
+        .append_pair("phone", phone)
+        .extend_pairs(url.query_pairs().filter(|(name, _)| name == "text"));
+    Some(chat.into())
+}
+
+/// `https://youtu.be/<id>?t=42` -> `https://www.youtube.com/watch?v=<id>&t=42`
+fn youtube(url: &Url) -> Option<String> {
+    let id = url.path().trim_matches('/');
+    if id.is_empty() || id.contains('/') {
+        return None;
+    }
+    let mut watch = Url::parse("https://www.youtube.com/watch").ok()?;
+    watch
+        .query_pairs_mut()
+        .append_pair("v", id)
+        .extend_pairs(url.query_pairs());
+    Some(watch.into())
+}
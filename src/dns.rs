@@ -0,0 +1,440 @@
+// Custom DNS resolution
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use hyper::{client::connect::dns::GaiResolver, service::Service};
+
+pub use hyper::client::connect::dns::Name;
+pub use reqwest::dns::{Addrs, Resolve, Resolving};
+
+#[cfg(feature = "doh")]
+use futures::future::join;
+#[cfg(feature = "doh")]
+use reqwest::Client;
+#[cfg(feature = "doh")]
+use serde_json::Value;
+
+#[cfg(feature = "doh")]
+use crate::{resolvers::verified_client, Error, Result};
+
+/// DNS record types queried for every host
+#[cfg(feature = "doh")]
+static RECORD_TYPES: [&str; 2] = ["A", "AAAA"];
+
+/// How long resolved addresses are cached by default
+static DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+/// A shared, type erased resolver that can be handed to reqwest
+#[derive(Clone)]
+pub(crate) struct SharedResolver(pub(crate) Arc<dyn Resolve>);
+
+impl Resolve for SharedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        self.0.resolve(name)
+    }
+}
+
+/// Which IP versions to connect over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpFamily {
+    /// Use addresses in the order the resolver returns them
+    #[default]
+    Any,
+    /// Try IPv4 addresses first (for hosts with broken AAAA records)
+    PreferIpv4,
+    /// Try IPv6 addresses first
+    PreferIpv6,
+    /// Only connect over IPv4
+    Ipv4Only,
+    /// Only connect over IPv6
+    Ipv6Only,
+}
+
+/// The operating system resolver (getaddrinfo)
+#[derive(Clone)]
+pub struct SystemResolver(GaiResolver);
+
+impl SystemResolver {
+    pub fn new() -> Self {
+        Self(GaiResolver::new())
+    }
+}
+
+impl Default for SystemResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let mut resolver = self.0.clone();
+        Box::pin(async move { Ok(Box::new(resolver.call(name).await?) as Addrs) })
+    }
+}
+
+/// Filters and orders the addresses of another resolver by IP family
+pub(crate) struct FamilyResolver {
+    pub(crate) inner: SharedResolver,
+    pub(crate) family: IpFamily,
+}
+
+impl Resolve for FamilyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolving = self.inner.resolve(name);
+        let family = self.family;
+        Box::pin(async move {
+            let (v4, v6): (Vec<SocketAddr>, Vec<SocketAddr>) =
+                resolving.await?.partition(SocketAddr::is_ipv4);
+            let addrs = match family {
+                IpFamily::Any | IpFamily::PreferIpv4 => [v4, v6].concat(),
+                IpFamily::PreferIpv6 => [v6, v4].concat(),
+                IpFamily::Ipv4Only => v4,
+                IpFamily::Ipv6Only => v6,
+            };
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether an address may be on a private network: loopback, private and
+/// shared (CGNAT) ranges, link-local (cloud metadata endpoints), site-local,
+/// unspecified, benchmarking, multicast and reserved ones, and the IPv6
+/// addresses embedding any of them (mapped, compatible, NAT64, 6to4)
+pub(crate) fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && b & 0xfe == 18)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_private(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+                    || first & 0xffc0 == 0xfec0
+            }
+        },
+    }
+}
+
+/// The IPv4 address an IPv6 one reaches: IPv4-mapped (`::ffff:a.b.c.d`),
+/// IPv4-compatible (`::a.b.c.d`), NAT64 (`64:ff9b::a.b.c.d`) or 6to4
+/// (`2002:aabb:ccdd::`)
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let [a, b, c, d, e, f, g, h] = ip.segments();
+    let ipv4 = |high: u16, low: u16| Some(Ipv4Addr::from(u32::from(high) << 16 | u32::from(low)));
+    match (a, b, c, d, e, f) {
+        _ if ip.is_loopback() || ip.is_unspecified() => None,
+        (0, 0, 0, 0, 0, 0) | (0, 0, 0, 0, 0, 0xffff) | (0x64, 0xff9b, 0, 0, 0, 0) => ipv4(g, h),
+        (0x2002, ..) => ipv4(b, c),
+        _ => None,
+    }
+}
+
+/// Lookup error of a host resolving to private addresses only
+#[derive(Debug)]
+pub(crate) struct PrivateAddress(pub(crate) String);
+
+impl fmt::Display for PrivateAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} resolves to private addresses only", self.0)
+    }
+}
+
+impl std::error::Error for PrivateAddress {}
+
+/// The host refused by [`PublicResolver`] behind an error of reqwest, if any
+pub(crate) fn refused_host(e: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if let Some(PrivateAddress(host)) = e.downcast_ref() {
+            return Some(host.clone());
+        }
+        source = e.source();
+    }
+    None
+}
+
+/// Drops the private addresses returned by another resolver, so that no
+/// short url can lead the expander into the local network
+pub(crate) struct PublicResolver {
+    pub(crate) inner: SharedResolver,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let resolving = self.inner.resolve(name);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = resolving
+                .await?
+                .filter(|addr| !is_private(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(Box::new(PrivateAddress(host)) as _);
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+tokio::task_local! {
+    /// How long the lookup made for the request being sent took
+    static LOOKUP: Cell<Option<Duration>>;
+}
+
+/// Send a request, timing the lookup its connection needed, if any: the
+/// lookup runs within the request, so concurrent requests to the same host
+/// each get their own
+pub(crate) async fn timed_lookup<F: Future>(request: F) -> (F::Output, Option<Duration>) {
+    LOOKUP
+        .scope(Cell::new(None), async {
+            let output = request.await;
+            (output, LOOKUP.with(Cell::get))
+        })
+        .await
+}
+
+/// Times the lookups of another resolver, for the request they are made for
+pub(crate) struct TimingResolver {
+    pub(crate) inner: SharedResolver,
+}
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolving = self.inner.resolve(name);
+        Box::pin(async move {
+            let started = Instant::now();
+            let addrs = resolving.await;
+            // lookups of connections made for no request (a pool race lost
+            // to an idle connection) are not timed
+            let _ = LOOKUP.try_with(|lookup| lookup.set(Some(started.elapsed())));
+            addrs
+        })
+    }
+}
+
+/// Resolved addresses by host, with the time they were resolved at
+type Cache = Arc<Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>>;
+
+/// Caches the addresses returned by another resolver, so bulk
+/// expansions against the same shorteners resolve each host once
+pub struct CachingResolver<R> {
+    inner: Arc<R>,
+    ttl: Duration,
+    cache: Cache,
+}
+
+impl<R: Resolve + 'static> CachingResolver<R> {
+    /// Cache the answers of `inner` for 5 minutes
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            ttl: DEFAULT_TTL,
+            cache: Default::default(),
+        }
+    }
+
+    /// How long answers are cached
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+impl<R: Resolve + 'static> Resolve for CachingResolver<R> {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_lowercase();
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&host)
+            .filter(|(resolved_at, _)| resolved_at.elapsed() < self.ttl)
+            .map(|(_, addrs)| addrs.clone());
+        if let Some(addrs) = cached {
+            return Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) });
+        }
+
+        let inner = self.inner.clone();
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = inner.resolve(name).await?.collect();
+            cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(host, (Instant::now(), addrs.clone()));
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(feature = "hickory")]
+pub use hickory::HickoryResolver;
+
+#[cfg(feature = "hickory")]
+mod hickory {
+    use std::net::SocketAddr;
+
+    use hickory_resolver::{
+        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+        TokioAsyncResolver,
+    };
+
+    use super::{Addrs, Name, Resolve, Resolving};
+
+    /// Resolves hostnames with hickory-resolver, which caches answers
+    /// and can be pointed at specific nameservers
+    #[derive(Clone)]
+    pub struct HickoryResolver(TokioAsyncResolver);
+
+    impl HickoryResolver {
+        /// Resolver using the given hickory configuration
+        pub fn new(config: ResolverConfig, options: ResolverOpts) -> Self {
+            Self(TokioAsyncResolver::tokio(config, options))
+        }
+
+        /// Resolver querying only the given nameservers (plain DNS over udp/tcp)
+        pub fn with_nameservers(nameservers: &[SocketAddr]) -> Self {
+            let group = nameservers
+                .iter()
+                .map(|ns| NameServerConfigGroup::from_ips_clear(&[ns.ip()], ns.port(), true))
+                .fold(NameServerConfigGroup::new(), |mut all, group| {
+                    all.merge(group);
+                    all
+                });
+            Self::new(
+                ResolverConfig::from_parts(None, vec![], group),
+                ResolverOpts::default(),
+            )
+        }
+    }
+
+    impl Resolve for HickoryResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            let resolver = self.0.clone();
+            Box::pin(async move {
+                let lookup = resolver.lookup_ip(name.as_str()).await?;
+                let addrs: Vec<SocketAddr> =
+                    lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+                Ok(Box::new(addrs.into_iter()) as Addrs)
+            })
+        }
+    }
+}
+
+/// Resolves hostnames with DNS-over-HTTPS (JSON API) instead of the system resolver
+#[cfg(feature = "doh")]
+#[derive(Clone)]
+pub struct DohResolver {
+    endpoint: Arc<str>,
+    timeout: Option<Duration>,
+    pub(crate) client: Client,
+}
+
+#[cfg(feature = "doh")]
+impl DohResolver {
+    /// Use the DoH JSON endpoint at `endpoint` (eg: `https://1.1.1.1/dns-query`);
+    /// its host is resolved with the system resolver, so prefer an IP address
+    pub fn new(endpoint: &str, timeout: Option<Duration>) -> Result<Self> {
+        Ok(Self {
+            endpoint: endpoint.into(),
+            timeout,
+            client: verified_client()?,
+        })
+    }
+
+    /// Cloudflare's resolver (1.1.1.1)
+    pub fn cloudflare() -> Result<Self> {
+        Self::new("https://1.1.1.1/dns-query", None)
+    }
+
+    /// Google's public resolver (8.8.8.8)
+    pub fn google() -> Result<Self> {
+        Self::new("https://8.8.8.8/resolve", None)
+    }
+
+    /// Resolve the host to all its IPv4 and IPv6 addresses; a failed query
+    /// of one family is an error only when the other failed too
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let (v4, v6) = join(
+            self.query(host, RECORD_TYPES[0]),
+            self.query(host, RECORD_TYPES[1]),
+        )
+        .await;
+        let addrs = match (v4, v6) {
+            (Err(e), Err(_)) => return Err(e),
+            (v4, v6) => [v4.unwrap_or_default(), v6.unwrap_or_default()].concat(),
+        };
+        if addrs.is_empty() {
+            Err(Error::Dns(format!("no address found for {}", host)))
+        } else {
+            Ok(addrs)
+        }
+    }
+
+    async fn query(&self, host: &str, record_type: &str) -> Result<Vec<IpAddr>> {
+        let mut request = self
+            .client
+            .get(&*self.endpoint)
+            .query(&[("name", host), ("type", record_type)])
+            .header("Accept", "application/dns-json");
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::Dns(format!(
+                "DoH provider returned {}",
+                response.status()
+            )));
+        }
+        let body: Value =
+            serde_json::from_str(&response.text().await?).map_err(|e| Error::Dns(e.to_string()))?;
+        Ok(addrs_from_response(&body))
+    }
+}
+
+#[cfg(feature = "doh")]
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0))) as _)
+        })
+    }
+}
+
+/// Addresses of the A/AAAA answers of a DoH JSON response
+#[cfg(feature = "doh")]
+pub(crate) fn addrs_from_response(response: &Value) -> Vec<IpAddr> {
+    response["Answer"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|answer| matches!(answer["type"].as_u64(), Some(1) | Some(28)))
+        .filter_map(|answer| answer["data"].as_str()?.parse().ok())
+        .collect()
+}
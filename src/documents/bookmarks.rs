@@ -0,0 +1,69 @@
+// Netscape bookmark files, as exported by the browsers
+use regex::Regex;
+
+use super::{expand_links, ExpandedDocument, Link};
+use crate::{resolvers::decode_entities, Expander};
+
+/// `<A HREF="...">title</A>`, the url and title captured
+static BOOKMARK: &str = r#"(?is)<a\s[^>]*?\bhref\s*=\s*"([^"]*)"[^>]*>(.*?)</a>"#;
+
+/// A bookmark of a Netscape bookmark file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+}
+
+/// The bookmarks of a Netscape bookmark file (`bookmarks.html`), as
+/// exported by Firefox, Chrome, Safari or Edge
+/// ## Example
+/// ```rust
+/// use urlexpand::documents::parse_bookmarks;
+///
+/// let html = r#"<DL><p>
+///     <DT><A HREF="https://bit.ly/3alqLKi" ADD_DATE="1600000000">Search &amp; more</A>
+/// </DL><p>"#;
+/// let bookmarks = parse_bookmarks(html);
+/// assert_eq!(bookmarks[0].url, "https://bit.ly/3alqLKi");
+/// assert_eq!(bookmarks[0].title, "Search & more");
+/// ```
+pub fn parse_bookmarks(html: &str) -> Vec<Bookmark> {
+    let Ok(pattern) = Regex::new(BOOKMARK) else {
+        return vec![];
+    };
+    pattern
+        .captures_iter(html)
+        .filter_map(|bookmark| {
+            Some(Bookmark {
+                title: decode_entities(bookmark.get(2)?.as_str().trim()),
+                url: decode_entities(bookmark.get(1)?.as_str().trim()),
+            })
+        })
+        .collect()
+}
+
+/// Expand the shortened bookmarks of a Netscape bookmark file; the file
+/// of the result can be imported back in the browser
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{documents::expand_bookmarks, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let html = std::fs::read_to_string("bookmarks.html")?;
+/// let expanded = expand_bookmarks(&Expander::builder().build()?, &html).await;
+/// std::fs::write("bookmarks-expanded.html", expanded.document)?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_bookmarks(expander: &Expander, html: &str) -> ExpandedDocument {
+    let links = match Regex::new(BOOKMARK) {
+        Ok(pattern) => pattern
+            .captures_iter(html)
+            .filter_map(|bookmark| Some(Link::at(html, bookmark.get(1)?.range())))
+            .collect(),
+        Err(_) => vec![],
+    };
+    expand_links(expander, html, links).await
+}
@@ -0,0 +1,166 @@
+// CSV files with a column of urls
+use futures::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt},
+    stream, StreamExt,
+};
+
+use super::{LinkExpansion, StreamSummary};
+use crate::{Error, Expander, Result};
+
+/// Columns appended to the rows of the CSV
+static COLUMNS: &str = "expanded_url,error";
+
+/// The next record of a CSV, with its line ending: one line or more, when
+/// a quoted field has line breaks
+async fn read_record<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<String>> {
+    let mut record = String::new();
+    loop {
+        if reader.read_line(&mut record).await? == 0 {
+            return Ok(Some(record).filter(|record| !record.is_empty()));
+        }
+        if record.matches('"').count().is_multiple_of(2) {
+            return Ok(Some(record));
+        }
+    }
+}
+
+/// The fields of a record, unquoted as RFC 4180 says
+fn fields(record: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(ch),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Quote a field as RFC 4180 says, when it needs it
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+/// A record without its line ending, and the line ending
+fn split_ending(record: &str) -> (&str, &str) {
+    let content = record.trim_end_matches(['\r', '\n']);
+    (content, &record[content.len()..])
+}
+
+/// Expand the urls of the `column` of a CSV, `concurrency` rows at a time,
+/// writing every row back with an `expanded_url` and an `error` column
+/// appended; the rows are written in order, as they are expanded, and are
+/// left as they were otherwise. The urls that are not shortened are their
+/// own `expanded_url`
+///
+/// ## Example
+/// ```rust,no_run
+/// use futures::io::{AllowStdIo, BufReader};
+/// use urlexpand::{documents::expand_csv, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let input = BufReader::new(AllowStdIo::new(std::fs::File::open("links.csv")?));
+/// let output = AllowStdIo::new(std::fs::File::create("links-expanded.csv")?);
+/// let expander = Expander::builder().build()?;
+/// let summary = expand_csv(&expander, input, output, "url", 8).await?;
+/// println!("{} rows, {} failures", summary.rows, summary.failures.len());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_csv<R, W>(
+    expander: &Expander,
+    mut reader: R,
+    mut writer: W,
+    column: &str,
+    concurrency: usize,
+) -> Result<StreamSummary>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let Some(header) = read_record(&mut reader).await? else {
+        return Err(Error::Config("empty CSV".into()));
+    };
+    let (names, ending) = split_ending(&header);
+    let index = fields(names)
+        .iter()
+        .position(|name| name.trim() == column)
+        .ok_or_else(|| Error::Config(format!("no `{column}` column in the CSV header")))?;
+    writer
+        .write_all(format!("{names},{COLUMNS}{ending}").as_bytes())
+        .await?;
+
+    let records = stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        match read_record(&mut reader).await {
+            Ok(Some(record)) => Some((Ok(record), Some(reader))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+    let rows = records
+        .map(|record| async move {
+            let record = record?;
+            let url = fields(split_ending(&record).0)
+                .into_iter()
+                .nth(index)
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            let result = match url.is_empty() || !expander.is_shortened(&url) {
+                true => None,
+                false => Some(expander.expand(&url).await),
+            };
+            Ok::<_, Error>((record, url, result))
+        })
+        .buffered(concurrency.max(1));
+    futures::pin_mut!(rows);
+
+    let mut summary = StreamSummary::default();
+    while let Some(row) = rows.next().await {
+        let (record, url, result) = row?;
+        let (content, ending) = split_ending(&record);
+        if content.is_empty() {
+            writer.write_all(record.as_bytes()).await?;
+            continue;
+        }
+        let (expanded, error) = match &result {
+            None => (url.clone(), String::new()),
+            Some(Ok(expanded)) => (expanded.url.clone(), String::new()),
+            Some(Err(e)) => (String::new(), e.to_string()),
+        };
+        writer
+            .write_all(
+                format!(
+                    "{content},{},{}{ending}",
+                    csv_field(&expanded),
+                    csv_field(&error)
+                )
+                .as_bytes(),
+            )
+            .await?;
+        summary.rows += 1;
+        if let Some(result @ Err(_)) = result {
+            summary.failures.push(LinkExpansion {
+                original: url,
+                result,
+            });
+        }
+    }
+    writer.flush().await?;
+    Ok(summary)
+}
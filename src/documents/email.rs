@@ -0,0 +1,257 @@
+// Emails (.eml) and mailboxes (mbox)
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+
+use super::{expand_shortened, LinkExpansion};
+use crate::{decoders, resolvers::decode_entities, Expander};
+
+/// The http(s) urls of a text, up to the first character that cannot be
+/// part of one
+static URL: &str = r#"(?i)\bhttps?://[^\s<>"'`\[\]{}|\\^]+"#;
+/// Wrappers decoded around a link at most (SafeLinks around urldefense...)
+static MAX_UNWRAPS: usize = 4;
+
+/// A link found in an email
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailLink {
+    /// The link, as found in the email (its character references decoded)
+    pub original: String,
+    /// The link without its SafeLinks, urldefense... wrappers, the same as
+    /// `original` when it had none
+    pub url: String,
+}
+
+/// The links of an email with their shortened urls expanded
+#[derive(Debug)]
+pub struct ExpandedEmail {
+    /// Every link of the email, once, in order
+    pub links: Vec<EmailLink>,
+    /// The expansion of every shortened (unwrapped) url, once per url, in
+    /// the order of the email
+    pub expansions: Vec<LinkExpansion>,
+}
+
+/// The headers of a message or part, unfolded, and its body
+fn split(message: &str) -> (Vec<(String, String)>, &str) {
+    // the end of the line of the boundary of a part
+    let message = message
+        .strip_prefix("\r\n")
+        .or_else(|| message.strip_prefix('\n'))
+        .unwrap_or(message);
+    let (head, body) = match (message.find("\r\n\r\n"), message.find("\n\n")) {
+        // no headers
+        _ if message.starts_with(['\r', '\n']) => ("", message),
+        (Some(crlf), Some(lf)) if lf < crlf => (&message[..lf], &message[lf + 2..]),
+        (Some(crlf), _) => (&message[..crlf], &message[crlf + 4..]),
+        (None, Some(lf)) => (&message[..lf], &message[lf + 2..]),
+        (None, None) => (message, ""),
+    };
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().into()));
+        }
+    }
+    (headers, body)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// A parameter of a header value, `boundary` of
+/// `multipart/alternative; boundary="abc"`
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Decode a quoted-printable body, its soft line breaks removed
+fn quoted_printable(body: &str) -> String {
+    let bytes = body.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'=' {
+            decoded.push(bytes[i]);
+            i += 1;
+        } else if bytes[i + 1..].starts_with(b"\r\n") {
+            i += 3;
+        } else if bytes[i + 1..].starts_with(b"\n") {
+            i += 2;
+        } else if let Some(byte) = body
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(b'=');
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into()
+}
+
+/// The text and HTML parts of a message (or of a part of it), decoded, and
+/// whether each one is HTML
+fn texts(message: &str, out: &mut Vec<(String, bool)>) {
+    let (headers, body) = split(message);
+    let content_type = header(&headers, "content-type").unwrap_or("text/plain");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if mime.starts_with("multipart/") {
+        let Some(boundary) = parameter(content_type, "boundary") else {
+            return;
+        };
+        let delimiter = format!("--{boundary}");
+        let mut parts = body.split(delimiter.as_str()).skip(1);
+        while let Some(part) = parts.next().filter(|part| !part.starts_with("--")) {
+            texts(part, out);
+        }
+        return;
+    }
+    if mime == "message/rfc822" {
+        return texts(body, out);
+    }
+    let html = mime == "text/html";
+    if !html && mime != "text/plain" {
+        return;
+    }
+    let encoding = header(&headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .to_lowercase();
+    let text = match encoding.as_str() {
+        "quoted-printable" => quoted_printable(body),
+        "base64" => {
+            let encoded: String = body.split_whitespace().collect();
+            match general_purpose::STANDARD.decode(encoded) {
+                Ok(bytes) => String::from_utf8_lossy(&bytes).into(),
+                Err(_) => return,
+            }
+        }
+        _ => body.into(),
+    };
+    out.push((text, html));
+}
+
+/// The messages of a mailbox, split on the `From ` lines following a blank
+/// line; a lone email is a mailbox of one message
+fn messages(mailbox: &str) -> Vec<&str> {
+    let mut starts = vec![0];
+    let mut offset = 0;
+    let mut blank = false;
+    for line in mailbox.split_inclusive('\n') {
+        if offset > 0 && blank && line.starts_with("From ") {
+            starts.push(offset);
+        }
+        blank = line.trim().is_empty();
+        offset += line.len();
+    }
+    starts.push(mailbox.len());
+    starts
+        .windows(2)
+        .map(|range| {
+            let message = &mailbox[range[0]..range[1]];
+            match message.starts_with("From ") {
+                // the separator line of the message
+                true => message.split_once('\n').map_or("", |(_, rest)| rest),
+                false => message,
+            }
+        })
+        .collect()
+}
+
+/// `url` without the link wrappers that carry their destination
+fn unwrap(url: &str) -> String {
+    let mut url = url.to_string();
+    for _ in 0..MAX_UNWRAPS {
+        match decoders::decode(&url) {
+            Some((_, decoded)) if decoded != url => url = decoded,
+            _ => break,
+        }
+    }
+    url
+}
+
+/// The links of an email (`.eml`) or of the messages of a mailbox (mbox),
+/// found in their text and HTML parts, with the link wrappers of the mail
+/// gateways (Microsoft SafeLinks, Proofpoint urldefense...) decoded
+/// ## Example
+/// ```rust
+/// use urlexpand::documents::parse_email;
+///
+/// let eml = "From: alice@example.com\r\n\
+///     Content-Type: text/plain\r\n\
+///     \r\n\
+///     See https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fbit.ly%2Fabc&data=05.\r\n";
+/// let links = parse_email(eml);
+/// assert_eq!(links[0].url, "https://bit.ly/abc");
+/// ```
+pub fn parse_email(raw: &str) -> Vec<EmailLink> {
+    let Ok(pattern) = Regex::new(URL) else {
+        return vec![];
+    };
+    let mut parts = vec![];
+    for message in messages(raw) {
+        texts(message, &mut parts);
+    }
+    let mut links: Vec<EmailLink> = vec![];
+    for (text, html) in &parts {
+        for found in pattern.find_iter(text) {
+            let found = found
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            let original = match html {
+                true => decode_entities(found),
+                false => found.into(),
+            };
+            if links.iter().all(|link| link.original != original) {
+                let url = unwrap(&original);
+                links.push(EmailLink { original, url });
+            }
+        }
+    }
+    links
+}
+
+/// Expand the shortened links of an email or mailbox, once their wrappers
+/// are decoded, for the triage of phishing reports
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{documents::expand_email, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let eml = std::fs::read_to_string("reported.eml")?;
+/// let expanded = expand_email(&Expander::builder().build()?, &eml).await;
+/// for expansion in &expanded.expansions {
+///     if let Ok(url) = &expansion.result {
+///         println!("{} -> {}", expansion.original, url.url);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_email(expander: &Expander, raw: &str) -> ExpandedEmail {
+    let links = parse_email(raw);
+    let expansions = expand_shortened(expander, links.iter().map(|link| link.url.as_str())).await;
+    ExpandedEmail { links, expansions }
+}
@@ -0,0 +1,103 @@
+// RSS 2.0 and Atom feeds
+use regex::Regex;
+
+use super::{expand_links, ExpandedDocument, Link};
+use crate::{resolvers::decode_entities, Expander};
+
+/// An `<item>` (RSS) or `<entry>` (Atom), its content captured
+static ITEM: &str = r"(?is)<(item|entry)\b[^>]*>(.*?)</(?:item|entry)>";
+/// `<title>...</title>`, CDATA or not
+static TITLE: &str = r"(?is)<title\b[^>]*>\s*(?:<!\[CDATA\[(.*?)\]\]>|([^<]*?))\s*</title>";
+/// The link of an RSS item, `<link>...</link>`, CDATA or not
+static RSS_LINK: &str = r"(?is)<link>\s*(?:<!\[CDATA\[\s*([^\]\s]*)\s*\]\]>|([^<\s]*))\s*</link>";
+/// The link of an Atom entry, `<link href="..."/>`, with its `rel` if any
+static ATOM_LINK: &str = r#"(?is)<link\s[^>]*?\bhref\s*=\s*["']([^"']*)["'][^>]*>"#;
+static REL: &str = r#"(?is)\brel\s*=\s*["']([^"']*)["']"#;
+
+/// An item of a feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedItem {
+    pub title: String,
+    /// The link of the item, or `None` when it has none
+    pub link: Option<String>,
+}
+
+/// The links of a feed: the text of RSS `<link>` elements and the `href`
+/// of the Atom `<link rel="alternate">` ones (those without `rel` too),
+/// but not the enclosures, comments or self links
+fn links(xml: &str) -> Vec<Link> {
+    let (Ok(rss), Ok(atom), Ok(rel)) =
+        (Regex::new(RSS_LINK), Regex::new(ATOM_LINK), Regex::new(REL))
+    else {
+        return vec![];
+    };
+    let rss = rss.captures_iter(xml).filter_map(|link| match link.get(1) {
+        // CDATA is neither decoded nor escaped
+        Some(cdata) => Some(Link::cdata(xml, cdata.range())),
+        None => Some(Link::at(xml, link.get(2)?.range())),
+    });
+    let atom = atom.captures_iter(xml).filter_map(|link| {
+        let tag = link.get(0)?.as_str();
+        let alternate = rel
+            .captures(tag)
+            .and_then(|rel| rel.get(1))
+            .is_none_or(|rel| rel.as_str().eq_ignore_ascii_case("alternate"));
+        let href = link.get(1).filter(|_| alternate)?;
+        Some(Link::at(xml, href.range()))
+    });
+    let mut links: Vec<Link> = rss.chain(atom).collect();
+    links.sort_by_key(|link| link.range.start);
+    links
+}
+
+/// The items of an RSS or Atom feed, in order
+/// ## Example
+/// ```rust
+/// use urlexpand::documents::parse_feed;
+///
+/// let xml = r#"<rss version="2.0"><channel>
+///   <item><title>Hello &amp; welcome</title><link>https://t.co/abc</link></item>
+/// </channel></rss>"#;
+/// let items = parse_feed(xml);
+/// assert_eq!(items[0].title, "Hello & welcome");
+/// assert_eq!(items[0].link.as_deref(), Some("https://t.co/abc"));
+/// ```
+pub fn parse_feed(xml: &str) -> Vec<FeedItem> {
+    let (Ok(item), Ok(title)) = (Regex::new(ITEM), Regex::new(TITLE)) else {
+        return vec![];
+    };
+    item.captures_iter(xml)
+        .filter_map(|item| {
+            let content = item.get(2)?.as_str();
+            let title = title
+                .captures(content)
+                .and_then(|title| match title.get(1) {
+                    Some(cdata) => Some(cdata.as_str().trim().to_string()),
+                    None => Some(decode_entities(title.get(2)?.as_str())),
+                })
+                .unwrap_or_default();
+            let link = links(content).into_iter().next().map(|link| link.url);
+            Some(FeedItem { title, link })
+        })
+        .collect()
+}
+
+/// Expand the shortened links of an RSS or Atom feed (the feedproxy links
+/// of FeedBurner, the t.co wrappers of tweets...), returning the feed with
+/// the canonical links of its items; the rest of the feed is left as it was
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{documents::expand_feed, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let xml = std::fs::read_to_string("feed.xml")?;
+/// let expanded = expand_feed(&Expander::builder().build()?, &xml).await;
+/// println!("{}", expanded.document);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_feed(expander: &Expander, xml: &str) -> ExpandedDocument {
+    expand_links(expander, xml, links(xml)).await
+}
@@ -0,0 +1,124 @@
+// Newline-delimited JSON (JSONL) records with a url
+use futures::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt},
+    stream, StreamExt,
+};
+use serde_json::{Map, Value};
+
+use super::{LinkExpansion, StreamSummary};
+use crate::{Error, Expander, Result};
+
+/// Keys added to the records
+static EXPANDED_URL: &str = "expanded_url";
+static ERROR: &str = "error";
+
+/// The record of a line with `expanded_url` and `error` added; the line is
+/// kept as it was written when it has neither key, rather than serialized
+/// again with its keys reordered
+fn enrich(line: &str, mut record: Map<String, Value>, expanded: Value, error: Value) -> String {
+    if record.contains_key(EXPANDED_URL) || record.contains_key(ERROR) {
+        record.insert(EXPANDED_URL.into(), expanded);
+        record.insert(ERROR.into(), error);
+        return Value::Object(record).to_string();
+    }
+    let object = line.trim_end();
+    let Some(content) = object.strip_suffix('}') else {
+        return object.into();
+    };
+    let separator = match record.is_empty() {
+        true => "",
+        false => ",",
+    };
+    format!("{content}{separator}\"{EXPANDED_URL}\":{expanded},\"{ERROR}\":{error}}}")
+}
+
+/// Expand the url at the JSON `pointer` (`/url`, `/link/href`...) of
+/// every record of a JSONL stream, `concurrency` records at a time, writing
+/// the records in order with an `expanded_url` and an `error` key added.
+/// Records are read only as fast as they are written, so a slow writer
+/// holds the reader back; the urls that are not shortened are their own
+/// `expanded_url`, and records without a url at `pointer` get `null`. Fails
+/// on a line that is not a JSON object
+///
+/// ## Example
+/// ```rust,no_run
+/// use futures::io::{AllowStdIo, BufReader};
+/// use urlexpand::{documents::expand_jsonl, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let input = BufReader::new(AllowStdIo::new(std::io::stdin()));
+/// let output = AllowStdIo::new(std::io::stdout());
+/// let expander = Expander::builder().build()?;
+/// expand_jsonl(&expander, input, output, "/entities/url", 16).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_jsonl<R, W>(
+    expander: &Expander,
+    reader: R,
+    mut writer: W,
+    pointer: &str,
+    concurrency: usize,
+) -> Result<StreamSummary>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let lines = stream::unfold(Some((reader, 0)), |state| async move {
+        let (mut reader, number) = state?;
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok((number + 1, line)), Some((reader, number + 1)))),
+            Err(e) => Some((Err(e.into()), None)),
+        }
+    });
+    let records = lines
+        .filter(|line| {
+            let blank = matches!(line, Ok((_, line)) if line.trim().is_empty());
+            futures::future::ready(!blank)
+        })
+        .map(|line: Result<(usize, String)>| async move {
+            let (number, line) = line?;
+            let value: Value = serde_json::from_str(&line).unwrap_or_default();
+            let url = value
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty());
+            let Value::Object(record) = value else {
+                return Err(Error::Config(format!("line {number} is not a JSON object")));
+            };
+            let result = match &url {
+                Some(url) if expander.is_shortened(url) => Some(expander.expand(url).await),
+                _ => None,
+            };
+            Ok((line, record, url, result))
+        })
+        .buffered(concurrency.max(1));
+    futures::pin_mut!(records);
+
+    let mut summary = StreamSummary::default();
+    while let Some(record) = records.next().await {
+        let (line, record, url, result) = record?;
+        let (expanded, error) = match (&url, &result) {
+            (None, _) => (Value::Null, Value::Null),
+            (Some(url), None) => (url.as_str().into(), Value::Null),
+            (_, Some(Ok(expanded))) => (expanded.url.as_str().into(), Value::Null),
+            (_, Some(Err(e))) => (Value::Null, e.to_string().into()),
+        };
+        let mut enriched = enrich(&line, record, expanded, error);
+        enriched.push('\n');
+        writer.write_all(enriched.as_bytes()).await?;
+        summary.rows += 1;
+        if let (Some(url), Some(result @ Err(_))) = (url, result) {
+            summary.failures.push(LinkExpansion {
+                original: url,
+                result,
+            });
+        }
+    }
+    writer.flush().await?;
+    Ok(summary)
+}
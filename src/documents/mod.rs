@@ -0,0 +1,164 @@
+// Expansion of the shortened links of whole documents
+use std::{collections::HashMap, ops::Range};
+
+use futures::{stream, StreamExt};
+
+use crate::{resolvers::decode_entities, ExpandedUrl, Expander, Result};
+
+mod bookmarks;
+mod csv;
+#[cfg(feature = "email")]
+mod email;
+#[cfg(feature = "feeds")]
+mod feeds;
+#[cfg(feature = "jsonl")]
+mod jsonl;
+mod sitemap;
+
+pub use bookmarks::{expand_bookmarks, parse_bookmarks, Bookmark};
+pub use csv::{csv_field, expand_csv};
+#[cfg(feature = "email")]
+pub use email::{expand_email, parse_email, EmailLink, ExpandedEmail};
+#[cfg(feature = "feeds")]
+pub use feeds::{expand_feed, parse_feed, FeedItem};
+#[cfg(feature = "jsonl")]
+pub use jsonl::expand_jsonl;
+pub use sitemap::{expand_sitemap, parse_sitemap};
+
+/// Shortened urls of a document expanded at the same time, on top of the
+/// limit of the expander (see `ExpanderBuilder::max_in_flight`)
+static CONCURRENCY: usize = 16;
+
+/// The expansion of a shortened link of a document
+#[derive(Debug)]
+pub struct LinkExpansion {
+    /// The link, as found in the document (its character references decoded)
+    pub original: String,
+    pub result: Result<ExpandedUrl>,
+}
+
+/// A document with its shortened links replaced by their expansion
+#[derive(Debug)]
+pub struct ExpandedDocument {
+    /// The document, unchanged but for the links that could be expanded
+    pub document: String,
+    /// The expansion of every shortened link, once per url, in the order of
+    /// the document; the links that failed are left as they were
+    pub expansions: Vec<LinkExpansion>,
+}
+
+/// What a stream of records (the rows of a CSV, the lines of a JSONL
+/// file) expanded one by one was made of
+#[derive(Debug, Default)]
+pub struct StreamSummary {
+    /// Records read, without the header of a CSV
+    pub rows: usize,
+    /// The shortened urls that could not be expanded, in the order of the
+    /// stream; their `expanded_url` is left empty
+    pub failures: Vec<LinkExpansion>,
+}
+
+/// A link of a document: where its url is written, and the url
+pub(crate) struct Link {
+    pub(crate) range: Range<usize>,
+    pub(crate) url: String,
+    /// Whether the url is written with its character references, as in
+    /// attributes and text, rather than raw as in CDATA
+    pub(crate) escaped: bool,
+}
+
+impl Link {
+    /// The link at `range` of `document`, with its character references decoded
+    pub(crate) fn at(document: &str, range: Range<usize>) -> Self {
+        let url = decode_entities(document[range.clone()].trim());
+        Self {
+            range,
+            url,
+            escaped: true,
+        }
+    }
+
+    /// The link at `range` of `document`, in a CDATA section
+    #[cfg(feature = "feeds")]
+    pub(crate) fn cdata(document: &str, range: Range<usize>) -> Self {
+        Self {
+            url: document[range.clone()].trim().into(),
+            range,
+            escaped: false,
+        }
+    }
+}
+
+/// Expand the shortened urls among `urls`, once per url, in the order of
+/// their first occurrence
+pub(crate) async fn expand_shortened<'a>(
+    expander: &Expander,
+    urls: impl IntoIterator<Item = &'a str>,
+) -> Vec<LinkExpansion> {
+    let mut shortened: Vec<&str> = vec![];
+    for url in urls {
+        if expander.is_shortened(url) && !shortened.contains(&url) {
+            shortened.push(url);
+        }
+    }
+    let mut results: HashMap<&str, Result<ExpandedUrl>> = stream::iter(shortened.iter().copied())
+        .map(|url| async move { (url, expander.expand(url).await) })
+        .buffer_unordered(CONCURRENCY)
+        .collect()
+        .await;
+    shortened
+        .into_iter()
+        .filter_map(|url| {
+            Some(LinkExpansion {
+                original: url.into(),
+                result: results.remove(url)?,
+            })
+        })
+        .collect()
+}
+
+/// Expand the shortened links of a document, writing their expansion with
+/// its XML special characters escaped (but in CDATA), for HTML attributes
+/// and XML alike
+pub(crate) async fn expand_links(
+    expander: &Expander,
+    document: &str,
+    links: Vec<Link>,
+) -> ExpandedDocument {
+    let expansions = expand_shortened(expander, links.iter().map(|link| link.url.as_str())).await;
+    let expanded: HashMap<&str, &str> = expansions
+        .iter()
+        .filter_map(|expansion| {
+            let url = expansion.result.as_ref().ok()?;
+            Some((expansion.original.as_str(), url.url.as_str()))
+        })
+        .collect();
+
+    let mut rewritten = String::with_capacity(document.len());
+    let mut end = 0;
+    for link in &links {
+        if let Some(url) = expanded.get(link.url.as_str()) {
+            rewritten.push_str(&document[end..link.range.start]);
+            if link.escaped {
+                rewritten.push_str(&escape(url));
+            } else {
+                rewritten.push_str(url);
+            }
+            end = link.range.end;
+        }
+    }
+    rewritten.push_str(&document[end..]);
+
+    ExpandedDocument {
+        document: rewritten,
+        expansions,
+    }
+}
+
+/// Escape the characters of a url that are special in XML and HTML attributes
+pub(crate) fn escape(url: &str) -> String {
+    url.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,54 @@
+// Sitemaps (sitemaps.org), and sitemap indexes
+use regex::Regex;
+
+use super::{expand_links, ExpandedDocument, Link};
+use crate::Expander;
+
+/// `<loc>...</loc>`, the url captured without its surrounding whitespace
+static LOC: &str = r"(?i)<loc>\s*([^<\s]*)\s*</loc>";
+
+fn locs(xml: &str) -> Vec<Link> {
+    match Regex::new(LOC) {
+        Ok(pattern) => pattern
+            .captures_iter(xml)
+            .filter_map(|loc| Some(Link::at(xml, loc.get(1)?.range())))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// The `<loc>` urls of a sitemap (or of a sitemap index), in order
+/// ## Example
+/// ```rust
+/// use urlexpand::documents::parse_sitemap;
+///
+/// let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+///   <url><loc>https://example.com/?a=1&amp;b=2</loc></url>
+/// </urlset>"#;
+/// assert_eq!(parse_sitemap(xml), ["https://example.com/?a=1&b=2"]);
+/// ```
+pub fn parse_sitemap(xml: &str) -> Vec<String> {
+    locs(xml).into_iter().map(|loc| loc.url).collect()
+}
+
+/// Expand the shortened `<loc>` urls of a sitemap, leaving the rest of the
+/// document as it was
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{documents::expand_sitemap, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let xml = std::fs::read_to_string("sitemap.xml")?;
+/// let expanded = expand_sitemap(&Expander::builder().build()?, &xml).await;
+/// for expansion in expanded.expansions.iter().filter(|e| e.result.is_err()) {
+///     println!("{} could not be expanded", expansion.original);
+/// }
+/// std::fs::write("sitemap.xml", expanded.document)?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn expand_sitemap(expander: &Expander, xml: &str) -> ExpandedDocument {
+    expand_links(expander, xml, locs(xml)).await
+}
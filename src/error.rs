@@ -1,3 +1,26 @@
+use std::{fmt, time::Duration};
+
+/// What a request was doing when it timed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutStage {
+    /// Connecting to the server (see `ExpanderBuilder::connect_timeout`)
+    Connect,
+    /// Reading the body of a response
+    Read,
+    /// Waiting for a response (see `ExpanderBuilder::timeout`)
+    Total,
+}
+
+impl fmt::Display for TimeoutStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutStage::Connect => "connect",
+            TimeoutStage::Read => "read",
+            TimeoutStage::Total => "total",
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, thiserror::Error)]
 pub enum Error {
     #[error("tokio runtime error")]
@@ -6,21 +29,79 @@ pub enum Error {
     ReqwestHeader(String),
     #[error("reqwest error")]
     Reqwest(String),
+    #[error("{stage} timeout after {elapsed:?}")]
+    Timeout {
+        /// Time spent on the expansion
+        elapsed: Duration,
+        stage: TimeoutStage,
+    },
+    #[error("connection failed: {0}")]
+    Connect(String),
+    #[error("reputation check failed: {0}")]
+    Reputation(String),
+    #[error("dns resolution failed: {0}")]
+    Dns(String),
+    #[error("disallowed scheme: {0}")]
+    DisallowedScheme(String),
+    #[error("redirect downgraded to http: {0}")]
+    Downgrade(String),
+    #[error("private address refused: {0}")]
+    PrivateAddress(String),
+    #[error("expansion needs network access")]
+    NetworkRequired,
+    #[error("service unavailable: {0}")]
+    ServiceUnavailable(String),
+    #[error("service blocked the request: {service} ({status})")]
+    ServiceBlocked {
+        service: String,
+        /// `403` or `429`; `503`, or the status of a page telling about
+        /// too many requests (usually `200`), for rlu.ru
+        status: u16,
+        /// How long the service asked to wait, from its `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+    #[error("link deleted or expired: {service}")]
+    LinkGone { service: String },
+    #[error("bot challenge of the service: {0}")]
+    BotChallenge(String),
+    #[error("service defunct, its domain is parked: {0}")]
+    ServiceDefunct(String),
+    #[error("service not allowed: {0}")]
+    ServiceDenied(String),
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("invalid cassette: {0}")]
+    Cassette(String),
+    #[error("{resolver}: page format not recognized")]
+    ParseFailure { resolver: &'static str },
+    #[error("too many redirects")]
+    TooManyRedirects,
+    #[error("too many nested shorteners: {}", chain.len())]
+    ChainTooDeep {
+        /// The short urls expanded in a row, outermost first
+        chain: Vec<String>,
+    },
     #[error("no string")]
     NoString,
     #[error("unknown error")]
     Unknown,
 }
 
+#[cfg(feature = "network")]
 impl From<reqwest::header::ToStrError> for Error {
     fn from(a: reqwest::header::ToStrError) -> Self {
         Self::ReqwestHeader(a.to_string())
     }
 }
 
+#[cfg(feature = "network")]
 impl From<reqwest::Error> for Error {
     fn from(a: reqwest::Error) -> Self {
-        Self::Reqwest(a.to_string())
+        if a.is_connect() {
+            Self::Connect(a.to_string())
+        } else {
+            Self::Reqwest(a.to_string())
+        }
     }
 }
 
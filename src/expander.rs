@@ -0,0 +1,1535 @@
+// Configurable URL Expander
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use futures::future::{try_join_all, BoxFuture, FutureExt};
+use reqwest::{
+    header::{HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LOCATION},
+    redirect::Policy,
+    Client, Method, Proxy, StatusCode,
+};
+use tokio::sync::Semaphore;
+use url::{Position, Url};
+
+#[cfg(feature = "config-file")]
+use crate::config::{seconds, ExpanderConfig};
+#[cfg(feature = "doh")]
+use crate::dns::DohResolver;
+use crate::dns::{
+    FamilyResolver, IpFamily, PublicResolver, Resolve, SharedResolver, SystemResolver,
+    TimingResolver,
+};
+#[cfg(feature = "urlscan")]
+use crate::reputation::Urlscan;
+#[cfg(feature = "virustotal")]
+use crate::reputation::VirusTotal;
+use crate::{
+    breaker::CircuitBreaker,
+    cache::{evict_oldest, ExpansionCache},
+    config::Config,
+    decoders, domain_matches_service, fingerprint, has_scheme, health,
+    heuristics::{self, RiskScore},
+    hsts,
+    normalize::Normalization,
+    parse_input,
+    reputation::{Detections, ReputationProvider, ScanSubmission, Verdict},
+    resolvers::{self, get_client_builder, host, not_found, Session, MAX_REDIRECTS},
+    services::{ascii_domain, which_service},
+    suggest::{redirects_short_code, Suggestions},
+    transport::{HttpRequest, HttpTransport, Middleware, MiddlewareTransport, PublicTransport},
+    validate, Error, Result,
+};
+
+/// Information about one request made while expanding
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopInfo {
+    /// Position of the hop in the chain, starting at 0
+    pub index: usize,
+    /// The requested url
+    pub url: String,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// Where the time of the hop went
+    pub timing: HopTiming,
+    /// `ETag` of the response, to revalidate the cached expansions
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub etag: Option<String>,
+    /// `Last-Modified` date of the response, when it has no `ETag`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub last_modified: Option<String>,
+    /// The hop went from https to plain http, a red flag of phishing chains
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub downgrade: bool,
+    /// Where the response redirected to, when it was a redirect
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub location: Option<String>,
+}
+
+/// Time spent on one hop
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HopTiming {
+    /// Resolving the host, when it was resolved for this hop (not for
+    /// an earlier hop, a pooled connection or an IP address)
+    pub dns: Option<Duration>,
+    /// Until the response headers arrived; connecting and the TLS handshake
+    /// are part of it, the HTTP client not reporting them separately
+    pub ttfb: Duration,
+    /// Until the response was done with: its headers, plus its body when
+    /// the resolver had to read the page
+    pub total: Duration,
+}
+
+/// Async callback invoked after every hop
+pub(crate) type HopCallback = Arc<dyn Fn(HopInfo) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// What to do with a redirect, as decided by a redirect policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectAction {
+    /// Follow the redirect
+    Continue,
+    /// Do not follow the redirect; its target becomes the expanded url
+    Stop,
+    /// Follow the redirect to this url instead
+    Rewrite(Url),
+}
+
+/// User supplied decision on every redirect
+pub(crate) type RedirectPolicy = Arc<dyn Fn(&Url, usize) -> RedirectAction + Send + Sync>;
+
+/// User supplied test of urls the known services do not cover
+pub(crate) type ShortenerPredicate = Arc<dyn Fn(&Url) -> bool + Send + Sync>;
+
+/// HTTP versions used to talk to the shorteners
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.1, or HTTP/2 when negotiated with ALPN
+    #[default]
+    Auto,
+    /// Only HTTP/1.1 (for servers with broken ALPN)
+    Http1Only,
+    /// HTTP/2 without negotiation, for servers known to speak it
+    Http2PriorKnowledge,
+}
+
+/// Compression formats of HTTP responses; zstd is not one of them, reqwest
+/// 0.11 having no decoder for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    Gzip,
+    Brotli,
+    Deflate,
+}
+
+impl ContentEncoding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// Schemes that expansions may lead to by default
+static DEFAULT_SCHEMES: [&str; 2] = ["http", "https"];
+
+/// Service of the urls on Bitly branded domains
+static BITLY: &str = "bit.ly";
+
+/// Unknown domains remembered as probed at most, the oldest probes being
+/// forgotten first
+static MAX_PROBED_DOMAINS: usize = 10_000;
+
+/// Short urls expanded in a row at most by default, with `expand_nested`
+static MAX_CHAIN_DEPTH: usize = 10;
+
+/// Request settings and user callbacks consulted by the resolvers
+pub(crate) struct Settings {
+    pub(crate) on_hop: Option<HopCallback>,
+    pub(crate) redirect_policy: Option<RedirectPolicy>,
+    /// `Accept-Encoding` of the requests that do not fetch a page
+    pub(crate) accept_encoding: Option<String>,
+    /// Schemes redirects and resolved urls may use
+    pub(crate) allowed_schemes: Vec<String>,
+    /// Fail on redirects from https to plain http
+    pub(crate) refuse_downgrades: bool,
+    /// Redirects followed before failing with `Error::TooManyRedirects`
+    pub(crate) max_redirects: usize,
+    /// Timeouts replacing the client one for some services
+    pub(crate) service_timeouts: HashMap<String, Duration>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            on_hop: None,
+            redirect_policy: None,
+            accept_encoding: None,
+            allowed_schemes: DEFAULT_SCHEMES.iter().map(|&s| s.into()).collect(),
+            refuse_downgrades: false,
+            max_redirects: MAX_REDIRECTS,
+            service_timeouts: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Refuse urls with a scheme outside of the allowlist (`javascript:`, `data:`, `file:`...)
+    pub(crate) fn check_scheme(&self, url: &Url) -> Result<()> {
+        if self.allowed_schemes.iter().any(|s| s == url.scheme()) {
+            Ok(())
+        } else {
+            Err(Error::DisallowedScheme(url.scheme().into()))
+        }
+    }
+}
+
+/// Result of expanding a shortened URL
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExpandedUrl {
+    /// The url as it was given to the expander
+    pub original: String,
+    /// The expanded (destination) url, with an ASCII (punycode) host
+    pub url: String,
+    /// The expanded url with a Unicode host, for display; comparing it to
+    /// `url` helps spotting homoglyph domains
+    pub display_url: String,
+    /// The shortener service that was used to resolve the url
+    pub service: String,
+    /// Scheme the short url was reached over; `http` when input without a
+    /// scheme had to fall back from https
+    pub scheme: String,
+    /// HTTP status of the expanded url, when it was fetched while expanding
+    /// or checked (see `ExpanderBuilder::check_destination`)
+    pub status: Option<u16>,
+    /// Every request made during the expansion, starting with the
+    /// validated short url
+    pub chain: Vec<HopInfo>,
+    /// Reputation verdict of the destination, when a
+    /// `ReputationProvider` is configured
+    pub verdict: Option<Verdict>,
+    /// VirusTotal detection counts of the destination, when a
+    /// VirusTotal lookup is configured and the url is known to it
+    pub detections: Option<Detections>,
+    /// urlscan.io scan of the destination, when submissions are configured
+    pub scan: Option<ScanSubmission>,
+    /// Reputation, VirusTotal and urlscan.io lookups that failed, leaving
+    /// their field empty without failing the expansion
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lookup_errors: Vec<String>,
+    /// Offline phishing heuristics of the destination
+    pub risk: RiskScore,
+}
+
+impl ExpandedUrl {
+    /// The destination is known to be gone (404 Not Found or 410 Gone)
+    pub fn is_dead(&self) -> bool {
+        matches!(self.status, Some(404 | 410))
+    }
+
+    /// A redirect of the chain went from https to plain http
+    pub fn has_downgrade(&self) -> bool {
+        self.chain.iter().any(|hop| hop.downgrade)
+    }
+}
+
+/// Services allowed, denied and added on top of the known ones
+#[derive(Default)]
+pub(crate) struct ServiceLists {
+    allow: Vec<String>,
+    deny: Vec<String>,
+    pub(crate) custom: Vec<String>,
+    predicate: Option<ShortenerPredicate>,
+}
+
+/// Where a short url led, before any post-processing
+struct Resolution {
+    validated_url: String,
+    service: String,
+    expanded_url: String,
+    scheme: String,
+    chain: Vec<HopInfo>,
+}
+
+/// Builder for an [`Expander`]
+#[derive(Default)]
+pub struct ExpanderBuilder {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    verify_tls: bool,
+    reputation: Option<Arc<dyn ReputationProvider>>,
+    check_each_hop: bool,
+    http_fallback: bool,
+    forward_query: bool,
+    normalization: Normalization,
+    services: ServiceLists,
+    cache: Option<(usize, Duration)>,
+    circuit_breaker: Option<(u32, Duration)>,
+    max_in_flight: Option<usize>,
+    transport: Option<Arc<dyn HttpTransport>>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    check_destination: bool,
+    upgrade_https: bool,
+    expand_nested: bool,
+    max_chain_depth: Option<usize>,
+    detect_branded_domains: bool,
+    suggest_services: bool,
+    decode_wrappers: bool,
+    offline: bool,
+    block_private_addresses: bool,
+    settings: Settings,
+    resolver: Option<SharedResolver>,
+    ip_family: IpFamily,
+    http_version: HttpVersion,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    #[cfg(feature = "virustotal")]
+    virustotal: Option<VirusTotal>,
+    #[cfg(feature = "urlscan")]
+    urlscan: Option<Urlscan>,
+}
+
+impl ExpanderBuilder {
+    /// Timeout applied to every request made while expanding
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout of the requests made to expand urls of one service, e.g.
+    /// longer for slow ad gateways (`adf.ly`) and shorter for plain redirects
+    /// ## Example
+    /// ```rust
+    /// use std::time::Duration;
+    /// use urlexpand::Expander;
+    ///
+    /// let expander = Expander::builder()
+    ///     .timeout(Duration::from_secs(5))
+    ///     .service_timeout("adf.ly", Duration::from_secs(30))
+    ///     .service_timeout("bit.ly", Duration::from_secs(2))
+    ///     .build();
+    /// assert!(expander.is_ok());
+    /// ```
+    pub fn service_timeout(mut self, service: &str, timeout: Duration) -> Self {
+        self.settings
+            .service_timeouts
+            .insert(ascii_domain(service), timeout);
+        self
+    }
+
+    /// Timeout of the connection phase of every request
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// `User-Agent` sent to the shorteners
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Check the TLS certificates of the shorteners and of the hops (default:
+    /// off, as many shorteners and landing pages have broken certificates)
+    pub fn verify_tls(mut self, verify: bool) -> Self {
+        self.verify_tls = verify;
+        self
+    }
+
+    /// Send every request through a proxy (`http://`, `https://` or `socks5://` url)
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Redirects followed before giving up with `Error::TooManyRedirects` (default: 10)
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.settings.max_redirects = max_redirects;
+        self
+    }
+
+    /// Apply the values set in a [`Config`], keeping the others as they are
+    pub fn config(mut self, config: &Config) -> Self {
+        if let Some(timeout) = config.timeout {
+            self = self.timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            self = self.proxy(proxy);
+        }
+        if let Some(user_agent) = &config.user_agent {
+            self = self.user_agent(user_agent);
+        }
+        if let Some(max_redirects) = config.max_redirects {
+            self = self.max_redirects(max_redirects);
+        }
+        self
+    }
+
+    /// Apply a configuration file, keeping the settings it does not mention
+    #[cfg(feature = "config-file")]
+    pub fn expander_config(mut self, config: &ExpanderConfig) -> Result<Self> {
+        if let Some(timeout) = config.timeout {
+            self = self.timeout(seconds("timeout", timeout)?);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            self = self.connect_timeout(seconds("connect_timeout", timeout)?);
+        }
+        self = self.config(&Config {
+            timeout: None,
+            proxy: config.proxy.clone(),
+            user_agent: config.user_agent.clone(),
+            max_redirects: config.max_redirects,
+        });
+        if !config.services.allow.is_empty() {
+            self = self.allow_services(&config.services.allow);
+        }
+        if !config.services.deny.is_empty() {
+            self = self.deny_services(&config.services.deny);
+        }
+        if !config.services.custom.is_empty() {
+            self = self.custom_services(&config.services.custom);
+        }
+        for (service, &timeout) in &config.timeouts {
+            self = self.service_timeout(service, seconds(service, timeout)?);
+        }
+        if let Some(cache) = &config.cache {
+            self = self.cache(cache.capacity, Duration::from_secs(cache.ttl));
+        }
+        Ok(self)
+    }
+
+    /// Only expand urls of these services (default: every known service)
+    pub fn allow_services<I, S>(mut self, services: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.services.allow = services
+            .into_iter()
+            .map(|s| ascii_domain(s.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Refuse to expand urls of these services with `Error::ServiceDenied`
+    pub fn deny_services<I, S>(mut self, services: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.services.deny = services
+            .into_iter()
+            .map(|s| ascii_domain(s.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Handle these domains (and their subdomains) as shorteners too,
+    /// following their redirects like for any unknown service
+    pub fn custom_services<I, S>(mut self, services: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.services.custom = services
+            .into_iter()
+            .map(|s| ascii_domain(s.as_ref()))
+            .collect();
+        self
+    }
+
+    /// Handle the urls `predicate` accepts as short urls too, on top of the
+    /// known and custom services; their domain is used as service name
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::Expander;
+    ///
+    /// let expander = Expander::builder()
+    ///     .shortener_predicate(|url| url.domain().is_some_and(|domain| domain.len() < 7))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(expander.is_shortened("https://ab.io/x"));
+    /// ```
+    pub fn shortener_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Url) -> bool + Send + Sync + 'static,
+    {
+        self.services.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Keep up to `capacity` successful expansions in memory for `ttl`,
+    /// answering repeated urls without any request
+    pub fn cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some((capacity, ttl));
+        self
+    }
+
+    /// After `threshold` consecutive failures of a service, fail its
+    /// expansions right away with `Error::ServiceUnavailable` for `cooldown`
+    /// instead of waiting for more timeouts; a single expansion then probes
+    /// the service, closing the circuit when it succeeds
+    pub fn circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((threshold, cooldown));
+        self
+    }
+
+    /// Expand at most `max` urls at the same time, the other expansions
+    /// waiting for their turn (default: no limit)
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max.max(1));
+        self
+    }
+
+    /// Send a HEAD request to the expanded url when its status is not known
+    /// from the expansion, following its redirects, to flag dead links
+    /// (404/410) behind working shorteners (default: off)
+    pub fn check_destination(mut self, check_destination: bool) -> Self {
+        self.check_destination = check_destination;
+        self
+    }
+
+    /// Hand out plain http destinations over https when their host is known
+    /// to support it: hosts on the HSTS preload list are upgraded right away,
+    /// others when a HEAD request over https succeeds (default: off)
+    pub fn upgrade_https(mut self, upgrade_https: bool) -> Self {
+        self.upgrade_https = upgrade_https;
+        self
+    }
+
+    /// Expand again while the destination is itself a short url or a link
+    /// wrapper, returning the first other url; the chain then holds the
+    /// hops of every shortener (default: off)
+    pub fn expand_nested(mut self, expand_nested: bool) -> Self {
+        self.expand_nested = expand_nested;
+        self
+    }
+
+    /// Short urls expanded in a row at most with `expand_nested`, failing
+    /// with `Error::ChainTooDeep` beyond (default: 10)
+    pub fn max_chain_depth(mut self, depth: usize) -> Self {
+        self.max_chain_depth = Some(depth.max(1));
+        self
+    }
+
+    /// Probe urls of unknown domains with a HEAD request, and expand the
+    /// ones answered by Bitly (branded short domains such as `nyti.ms`) as
+    /// bit.ly links; every domain is probed once, the last 10,000 probed
+    /// being remembered (default: off)
+    pub fn detect_branded_domains(mut self, detect: bool) -> Self {
+        self.detect_branded_domains = detect;
+        self
+    }
+
+    /// Keep track of the unlisted domains met in the expansion chains that
+    /// redirect short codes to other domains, for
+    /// `Expander::suggested_services`; up to 10,000 domains, the least
+    /// recently seen being forgotten first (default: off)
+    pub fn suggest_services(mut self, suggest: bool) -> Self {
+        self.suggest_services = suggest;
+        self
+    }
+
+    /// Decode the link wrappers that carry their destination (SafeLinks,
+    /// urldefense, google `/url`, `l.facebook.com`, `wa.me`, `youtu.be`...)
+    /// without any request, rather than sending them to the network
+    /// resolvers like any other url (default: off, always on when offline)
+    pub fn decode_wrappers(mut self, decode: bool) -> Self {
+        self.decode_wrappers = decode;
+        self
+    }
+
+    /// Never touch the network: only decode the link wrappers that carry
+    /// their destination (SafeLinks, urldefense, google `/url`, `wa.me`,
+    /// `youtu.be`...) and fail with `Error::NetworkRequired` for anything
+    /// else; reputation checks and destination checks are skipped
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Send the requests with another HTTP stack than the built-in reqwest
+    /// client; the timeout, proxy, DNS, pool and HTTP version options only
+    /// apply to the built-in client
+    pub fn transport<T: HttpTransport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Run every request and response through a middleware; middlewares
+    /// run in the order they are added, around the transport
+    pub fn middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Resolve the hosts of every request with DNS-over-HTTPS
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::{dns::DohResolver, Expander};
+    ///
+    /// let expander = Expander::builder()
+    ///     .dns_over_https(DohResolver::cloudflare().unwrap())
+    ///     .build();
+    /// assert!(expander.is_ok());
+    /// ```
+    #[cfg(feature = "doh")]
+    pub fn dns_over_https(self, resolver: DohResolver) -> Self {
+        self.dns_resolver(resolver)
+    }
+
+    /// Resolve the hosts of every request with a custom resolver,
+    /// eg: a `CachingResolver` or a `HickoryResolver` pinned to some nameservers
+    /// ## Example
+    /// ```ignore
+    /// use urlexpand::{dns::{CachingResolver, HickoryResolver}, Expander};
+    ///
+    /// let nameservers = ["9.9.9.9:53".parse().unwrap()];
+    /// let expander = Expander::builder()
+    ///     .dns_resolver(CachingResolver::new(HickoryResolver::with_nameservers(&nameservers)))
+    ///     .build()?;
+    /// ```
+    pub fn dns_resolver<R: Resolve + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Some(SharedResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Which IP versions to connect over (default: any, in resolver order)
+    pub fn ip_family(mut self, ip_family: IpFamily) -> Self {
+        self.ip_family = ip_family;
+        self
+    }
+
+    /// HTTP versions to use (default: negotiated)
+    pub fn http_version(mut self, http_version: HttpVersion) -> Self {
+        self.http_version = http_version;
+        self
+    }
+
+    /// Maximum idle connections kept open per host
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How long idle connections are kept open (default: 90 seconds)
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Send TCP keepalive probes on idle connections at this interval
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Compression formats offered to the shorteners (default: all of them);
+    /// an empty list asks for uncompressed responses. Pages parsed by the
+    /// resolvers always accept every format and are decompressed transparently.
+    /// The formats apply to every expansion of the expander, there is no
+    /// setting per expansion
+    pub fn content_encodings<I>(mut self, encodings: I) -> Self
+    where
+        I: IntoIterator<Item = ContentEncoding>,
+    {
+        let encodings: Vec<&str> = encodings.into_iter().map(|e| e.as_str()).collect();
+        self.settings.accept_encoding = Some(if encodings.is_empty() {
+            "identity".into()
+        } else {
+            encodings.join(", ")
+        });
+        self
+    }
+
+    /// Fail with `Error::Downgrade` on a redirect from https to plain http,
+    /// rather than only flagging it in the chain (default: follow it)
+    pub fn refuse_downgrades(mut self, refuse: bool) -> Self {
+        self.settings.refuse_downgrades = refuse;
+        self
+    }
+
+    /// Schemes a redirect or an expanded url may use (default: http & https);
+    /// anything else fails the expansion with `Error::DisallowedScheme`
+    pub fn allowed_schemes<I, S>(mut self, schemes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.settings.allowed_schemes = schemes
+            .into_iter()
+            .map(|s| s.into().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Check the expanded url against a reputation service
+    pub fn reputation_provider<P: ReputationProvider + 'static>(mut self, provider: P) -> Self {
+        self.reputation = Some(Arc::new(provider));
+        self
+    }
+
+    /// Also check every intermediate url of the chain, the verdict
+    /// being the worst one found (default: only the destination)
+    pub fn check_each_hop(mut self, check_each_hop: bool) -> Self {
+        self.check_each_hop = check_each_hop;
+        self
+    }
+
+    /// Retry urls given without a scheme over plain http when https fails
+    /// to connect, for old shorteners without TLS (default: off, https only)
+    pub fn http_fallback(mut self, http_fallback: bool) -> Self {
+        self.http_fallback = http_fallback;
+        self
+    }
+
+    /// Refuse to connect to private addresses (loopback, private ranges,
+    /// link-local cloud metadata endpoints...) with
+    /// `Error::PrivateAddress`, so that untrusted short urls cannot reach
+    /// the local network (default: off). Hosts given as IP addresses are
+    /// checked for any transport; host names are checked once resolved by
+    /// the built-in client, except behind a proxy, which resolves them
+    pub fn block_private_addresses(mut self, block: bool) -> Self {
+        self.block_private_addresses = block;
+        self
+    }
+
+    /// Carry the query parameters and fragment of the short url
+    /// (`bit.ly/x?utm_source=a#top`) over to the expanded url, for the ones
+    /// the destination does not already define (default: off)
+    pub fn forward_query(mut self, forward_query: bool) -> Self {
+        self.forward_query = forward_query;
+        self
+    }
+
+    /// Normalize the expanded url, e.g. to use it as a dedup key
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::{Expander, Normalization};
+    ///
+    /// let expander = Expander::builder()
+    ///     .normalization(Normalization {
+    ///         sort_query: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
+    /// assert!(expander.is_ok());
+    /// ```
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Look up the expanded url on VirusTotal and attach the detection counts
+    #[cfg(feature = "virustotal")]
+    pub fn virustotal(mut self, virustotal: VirusTotal) -> Self {
+        self.virustotal = Some(virustotal);
+        self
+    }
+
+    /// Submit the expanded url to urlscan.io and attach the scan to the result
+    #[cfg(feature = "urlscan")]
+    pub fn urlscan(mut self, urlscan: Urlscan) -> Self {
+        self.urlscan = Some(urlscan);
+        self
+    }
+
+    /// Call `on_hop` after each request made while expanding,
+    /// e.g. to show progress or log every intermediate url
+    pub fn on_hop<F, Fut>(mut self, on_hop: F) -> Self
+    where
+        F: Fn(HopInfo) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.settings.on_hop = Some(Arc::new(move |hop| on_hop(hop).boxed()));
+        self
+    }
+
+    /// Decide what to do with every redirect: `policy` gets the redirect
+    /// target and the index of the hop that redirected to it
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::{is_shortened, Expander, RedirectAction};
+    ///
+    /// // stop at the first url that is not a shortener
+    /// let expander = Expander::builder()
+    ///     .redirect_policy(|url, _hop| {
+    ///         if is_shortened(url.as_str()) {
+    ///             RedirectAction::Continue
+    ///         } else {
+    ///             RedirectAction::Stop
+    ///         }
+    ///     })
+    ///     .build();
+    /// assert!(expander.is_ok());
+    /// ```
+    pub fn redirect_policy<F>(mut self, policy: F) -> Self
+    where
+        F: Fn(&Url, usize) -> RedirectAction + Send + Sync + 'static,
+    {
+        self.settings.redirect_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Build the Expander
+    pub fn build(self) -> Result<Expander> {
+        let mut transport = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => Arc::new(self.client()?),
+        };
+        if !self.middlewares.is_empty() {
+            transport = Arc::new(MiddlewareTransport {
+                middlewares: self.middlewares,
+                transport,
+            });
+        }
+        if self.block_private_addresses {
+            transport = Arc::new(PublicTransport { transport });
+        }
+        Ok(Expander {
+            inner: Arc::new(Inner {
+                transport,
+                reputation: self.reputation,
+                check_each_hop: self.check_each_hop,
+                http_fallback: self.http_fallback,
+                forward_query: self.forward_query,
+                normalization: self.normalization,
+                services: self.services,
+                cache: self
+                    .cache
+                    .map(|(capacity, ttl)| ExpansionCache::new(capacity, ttl)),
+                in_flight: self.max_in_flight.map(Semaphore::new),
+                check_destination: self.check_destination,
+                upgrade_https: self.upgrade_https,
+                nested: self
+                    .expand_nested
+                    .then(|| self.max_chain_depth.unwrap_or(MAX_CHAIN_DEPTH)),
+                detect_branded_domains: self.detect_branded_domains,
+                branded: Mutex::new(HashMap::new()),
+                suggestions: self.suggest_services.then(Suggestions::default),
+                decode_wrappers: self.decode_wrappers,
+                offline: self.offline,
+                breaker: self
+                    .circuit_breaker
+                    .map(|(threshold, cooldown)| CircuitBreaker::new(threshold, cooldown)),
+                settings: self.settings,
+                #[cfg(feature = "virustotal")]
+                virustotal: self.virustotal,
+                #[cfg(feature = "urlscan")]
+                urlscan: self.urlscan,
+            }),
+        })
+    }
+
+    /// The reqwest client used when no other transport is given
+    pub(crate) fn client(&self) -> Result<Client> {
+        let mut client = get_client_builder(self.timeout).redirect(Policy::none());
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            client = client.user_agent(user_agent);
+        }
+        if let Some(proxy) = &self.proxy {
+            client = client.proxy(Proxy::all(proxy)?);
+        }
+        if self.verify_tls {
+            client = client.danger_accept_invalid_certs(false);
+        }
+        let mut resolver = self
+            .resolver
+            .clone()
+            .unwrap_or_else(|| SharedResolver(Arc::new(SystemResolver::new())));
+        if self.ip_family != IpFamily::Any {
+            resolver = SharedResolver(Arc::new(FamilyResolver {
+                inner: resolver,
+                family: self.ip_family,
+            }));
+        }
+        if self.block_private_addresses {
+            resolver = SharedResolver(Arc::new(PublicResolver { inner: resolver }));
+        }
+        client = client.dns_resolver(Arc::new(TimingResolver { inner: resolver }));
+        if let Some(max) = self.pool_max_idle_per_host {
+            client = client.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            client = client.pool_idle_timeout(timeout);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            client = client.tcp_keepalive(interval);
+        }
+        client = match self.http_version {
+            HttpVersion::Auto => client,
+            HttpVersion::Http1Only => client.http1_only(),
+            HttpVersion::Http2PriorKnowledge => client.http2_prior_knowledge(),
+        };
+        // hosts given as IP addresses are not resolved, binding
+        // the local address also keeps them to the wanted family
+        match self.ip_family {
+            IpFamily::Ipv4Only => client = client.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpFamily::Ipv6Only => client = client.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            _ => {}
+        }
+        Ok(client.build()?)
+    }
+}
+
+pub(crate) struct Inner {
+    pub(crate) transport: Arc<dyn HttpTransport>,
+    reputation: Option<Arc<dyn ReputationProvider>>,
+    check_each_hop: bool,
+    http_fallback: bool,
+    forward_query: bool,
+    normalization: Normalization,
+    pub(crate) services: ServiceLists,
+    cache: Option<ExpansionCache>,
+    breaker: Option<CircuitBreaker>,
+    in_flight: Option<Semaphore>,
+    check_destination: bool,
+    upgrade_https: bool,
+    /// Short urls expanded in a row at most, when expanding nested ones
+    nested: Option<usize>,
+    detect_branded_domains: bool,
+    /// Unknown domains probed, when, and whether they are Bitly branded domains
+    branded: Mutex<HashMap<String, (Instant, bool)>>,
+    /// Unlisted domains seen in the chains, with `suggest_services`
+    pub(crate) suggestions: Option<Suggestions>,
+    decode_wrappers: bool,
+    pub(crate) offline: bool,
+    pub(crate) settings: Settings,
+    #[cfg(feature = "virustotal")]
+    virustotal: Option<VirusTotal>,
+    #[cfg(feature = "urlscan")]
+    urlscan: Option<Urlscan>,
+}
+
+/// A reusable, configured URL expander; cheap to clone
+#[derive(Clone)]
+pub struct Expander {
+    pub(crate) inner: Arc<Inner>,
+}
+
+impl Expander {
+    pub fn builder() -> ExpanderBuilder {
+        //! Start configuring a new Expander
+        //! ## Example
+        //! ```rust
+        //! use std::time::Duration;
+        //! use urlexpand::Expander;
+        //!
+        //! let expander = Expander::builder()
+        //!     .timeout(Duration::from_secs(10))
+        //!     .build();
+        //! assert!(expander.is_ok());
+        //! ```
+        ExpanderBuilder::default()
+    }
+
+    pub fn is_shortened<U: AsRef<str>>(&self, url: U) -> bool {
+        //! Whether the expander handles the url as a short url: a known
+        //! service, a custom one, or one accepted by the shortener predicate
+        self.service_of(url.as_ref()).is_some()
+    }
+
+    pub async fn is_shortened_async<U: AsRef<str>>(&self, url: U) -> bool {
+        //! Like `is_shortened`, asking the domain of a url it does not know
+        //! with a single HEAD request: a short url when Bitly answers it (the
+        //! domain is then expanded as a bit.ly one), or when its short path
+        //! (`/aB3x`) redirects to another domain; never when offline
+        //! ## Example
+        //! ```ignore
+        //!  use urlexpand::Expander;
+        //!
+        //!  let expander = Expander::builder().build()?;
+        //!  assert!(expander.is_shortened_async("https://nyti.ms/3alqLKi").await);
+        //! ```
+        let url = url.as_ref();
+        if self.is_shortened(url) {
+            return true;
+        }
+        if self.inner.offline {
+            return false;
+        }
+        let Some(parsed) = parse_input(url) else {
+            return false;
+        };
+        let Some(domain) = parsed.domain().map(ascii_domain) else {
+            return false;
+        };
+        let head = HttpRequest::new(Method::HEAD, parsed.clone());
+        let Ok(response) = self.inner.transport.send(head).await else {
+            return false;
+        };
+        let bitly = fingerprint::is_bitly(&response.headers);
+        self.set_branded(domain, bitly);
+        let location = response
+            .headers
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok());
+        bitly || redirects_short_code(&parsed, response.status.as_u16(), location)
+    }
+
+    pub async fn expand<U: AsRef<str>>(&self, url: U) -> Result<ExpandedUrl> {
+        //! Expand a shortened URL, given as `&str`, `String` or `Url`
+        //! ## Example
+        //! ```ignore
+        //!  use urlexpand::Expander;
+        //!
+        //!  let expander = Expander::builder().build()?;
+        //!  let expanded = expander.expand("https://bit.ly/3alqLKi").await?;
+        //!  assert_eq!(expanded.url, "https://www.google.com/");
+        //! ```
+        let url = url.as_ref();
+        if let Some(cache) = &self.inner.cache {
+            let cached = cache.get(url);
+            #[cfg(feature = "metrics")]
+            metrics::counter!(
+                "urlexpand_cache_lookups_total",
+                "result" => if cached.is_some() { "hit" } else { "miss" }
+            )
+            .increment(1);
+            if let Some(cached) = cached {
+                return Ok(cached);
+            }
+            if let Some(expired) = cache.get_expired(url) {
+                if self.not_modified(&expired).await {
+                    cache.insert(url, &expired);
+                    return Ok(expired);
+                }
+            }
+        }
+        let _permit = match &self.inner.in_flight {
+            Some(in_flight) => Some(in_flight.acquire().await.map_err(|_| Error::Unknown)?),
+            None => None,
+        };
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let result = self.expand_traced(url).await;
+        #[cfg(not(feature = "tracing"))]
+        let result = self.run(url).await;
+        #[cfg(feature = "metrics")]
+        record_metrics(url, &result, started.elapsed());
+        if let (Some(cache), Ok(expanded)) = (&self.inner.cache, &result) {
+            cache.insert(url, expanded);
+        }
+        result
+    }
+
+    /// Expand within an `expand` span recording the service, hops, status & elapsed time
+    #[cfg(feature = "tracing")]
+    async fn expand_traced(&self, url: &str) -> Result<ExpandedUrl> {
+        use tracing::{field::Empty, Instrument};
+
+        let span = tracing::info_span!(
+            "expand",
+            url,
+            service = Empty,
+            hops = Empty,
+            status = Empty,
+            elapsed_ms = Empty
+        );
+        let started = Instant::now();
+        let result = self.run(url).instrument(span.clone()).await;
+        span.record("elapsed_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok(expanded) => {
+                span.record("service", expanded.service.as_str());
+                span.record("hops", expanded.chain.len());
+                if let Some(hop) = expanded.chain.last() {
+                    span.record("status", hop.status);
+                }
+                tracing::debug!(parent: &span, destination = %expanded.url, "expanded");
+            }
+            Err(e) => tracing::warn!(parent: &span, error = %e, "expansion failed"),
+        }
+        result
+    }
+
+    async fn run(&self, url: &str) -> Result<ExpandedUrl> {
+        let Resolution {
+            validated_url,
+            service,
+            mut expanded_url,
+            scheme,
+            mut chain,
+        } = self.unwrap(url).await?;
+        if let Some(max_depth) = self.inner.nested {
+            let mut shorteners = vec![validated_url.clone()];
+            while self.is_shortened(&expanded_url) || self.decode(&expanded_url).is_some() {
+                if shorteners.len() >= max_depth {
+                    shorteners.push(expanded_url);
+                    return Err(Error::ChainTooDeep { chain: shorteners });
+                }
+                let nested = self.unwrap(&expanded_url).await?;
+                shorteners.push(nested.validated_url);
+                chain.extend(nested.chain);
+                expanded_url = nested.expanded_url;
+            }
+        }
+        if let Some(suggestions) = &self.inner.suggestions {
+            suggestions.record(&chain, |url| {
+                self.is_shortened(url) || decoders::decode(url).is_some()
+            });
+        }
+        if let Ok(parsed) = Url::parse(&expanded_url) {
+            self.inner.settings.check_scheme(&parsed)?;
+        }
+        let mut status = chain
+            .last()
+            .filter(|hop| hop.url == expanded_url)
+            .map(|hop| hop.status);
+        if status.is_none() && self.inner.check_destination && !self.inner.offline {
+            status = self.destination_status(&expanded_url).await;
+        }
+        if self.inner.forward_query {
+            expanded_url = forward_query(&validated_url, &expanded_url);
+        }
+        if self.inner.upgrade_https {
+            if let Some(upgraded) = self.https_upgrade(&expanded_url).await {
+                expanded_url = upgraded;
+            }
+        }
+        if self.inner.normalization != Normalization::default() {
+            expanded_url = self.inner.normalization.apply(&expanded_url);
+        }
+
+        let mut expanded = ExpandedUrl {
+            original: url.into(),
+            risk: heuristics::score(&expanded_url),
+            display_url: display_url(&expanded_url),
+            url: expanded_url,
+            service,
+            scheme,
+            status,
+            chain,
+            verdict: None,
+            detections: None,
+            scan: None,
+            lookup_errors: vec![],
+        };
+        if self.inner.offline {
+            return Ok(expanded);
+        }
+        if let Some(provider) = &self.inner.reputation {
+            match self.verdict(provider.as_ref(), &expanded).await {
+                Ok(verdict) => expanded.verdict = Some(verdict),
+                Err(e) => expanded.lookup_errors.push(format!("reputation: {}", e)),
+            }
+        }
+        #[cfg(feature = "virustotal")]
+        if let Some(virustotal) = &self.inner.virustotal {
+            match virustotal.lookup(&expanded.url).await {
+                Ok(detections) => expanded.detections = detections,
+                Err(e) => expanded.lookup_errors.push(format!("virustotal: {}", e)),
+            }
+        }
+        #[cfg(feature = "urlscan")]
+        if let Some(urlscan) = &self.inner.urlscan {
+            match urlscan.submit(&expanded.url).await {
+                Ok(scan) => expanded.scan = Some(scan),
+                Err(e) => expanded.lookup_errors.push(format!("urlscan: {}", e)),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// The destination of a link wrapper, and its name, when wrappers are
+    /// decoded
+    fn decode(&self, url: &str) -> Option<(&'static str, String)> {
+        (self.inner.decode_wrappers || self.inner.offline)
+            .then(|| decoders::decode(url))
+            .flatten()
+    }
+
+    /// Where a url leads: decoded when it is a link wrapper (and wrappers
+    /// are decoded), followed through its shortener otherwise
+    async fn unwrap(&self, url: &str) -> Result<Resolution> {
+        match self.decode(url) {
+            Some((wrapper, decoded)) => {
+                self.check_lists(wrapper)?;
+                let validated = parse_input(url).ok_or(Error::NoString)?;
+                Ok(Resolution {
+                    validated_url: validated.as_str().into(),
+                    service: wrapper.into(),
+                    expanded_url: decoded,
+                    scheme: validated.scheme().into(),
+                    chain: vec![],
+                })
+            }
+            None if self.inner.offline => Err(Error::NetworkRequired),
+            None => self.resolve(url).await,
+        }
+    }
+
+    /// Follow a short url through its shortener
+    async fn resolve(&self, url: &str) -> Result<Resolution> {
+        let (validated_url, service) = match self.service_of(url) {
+            Some(found) => found,
+            None => self.probe_branded(url).await.ok_or(Error::NoString)?,
+        };
+        self.check_lists(&service)?;
+        let session = Session::new(self.inner.transport.as_ref(), &self.inner.settings)
+            .timeout(self.inner.settings.service_timeouts.get(&service).copied());
+        let resolved = async {
+            if has_scheme(url) {
+                let expanded = resolvers::unshort(&service, &validated_url, &session).await?;
+                Ok((
+                    expanded,
+                    validated_url.split(':').next().unwrap_or("https").into(),
+                ))
+            } else {
+                let fallback = self.inner.http_fallback;
+                resolvers::unshort_scheme_less(&service, &validated_url, &session, fallback).await
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let resolved = tracing::Instrument::instrument(
+            resolved,
+            tracing::debug_span!("resolve", service = service.as_str()),
+        );
+        let probe = match &self.inner.breaker {
+            Some(breaker) => breaker.check(&service)?,
+            None => None,
+        };
+        let resolved = resolved.await;
+        // an error status of the shortener only fails the expansion when it
+        // was not an interstitial the resolver could read
+        let left_shortener = matches!(
+            &resolved,
+            Ok((expanded, _)) if host(expanded) != host(&validated_url)
+        );
+        // where the shortener led, even when the resolver found nothing there
+        let landed = match &resolved {
+            Ok((expanded, _)) => Some(expanded.clone()),
+            Err(_) => session.last_url(),
+        };
+        let page = session.shortener_page().filter(|_| !left_shortener);
+        let unknown = not_found::is_unknown_link(
+            &service,
+            &validated_url,
+            landed.as_deref(),
+            page.as_deref(),
+        );
+        // a link to a domain listing is no parked shortener, unless the home
+        // page of the shortener is sent there too
+        let parked = page.as_deref().is_some_and(health::is_parking_page)
+            || (session.leads_to_parking() && self.home_parked(&validated_url).await);
+        let resolved = match session.shortener_reply() {
+            _ if parked => Err(Error::ServiceDefunct(service.clone())),
+            _ if unknown => Err(Error::LinkGone {
+                service: service.clone(),
+            }),
+            _ if left_shortener => resolved,
+            // challenge pages come with a 403 or a 429 too
+            _ if matches!(resolved, Err(Error::BotChallenge(_))) => resolved,
+            // interstitials of some services (adf.ly) come with a 403
+            Some((403, _)) if matches!(resolved, Err(Error::ParseFailure { .. })) => resolved,
+            Some((status @ (403 | 429), retry_after)) => Err(Error::ServiceBlocked {
+                service: service.clone(),
+                status,
+                retry_after,
+            }),
+            _ if matches!(session.first_status(), Some(404 | 410)) => Err(Error::LinkGone {
+                service: service.clone(),
+            }),
+            _ => resolved,
+        };
+        if let Some(breaker) = &self.inner.breaker {
+            // a deleted link is no sign of an unhealthy service
+            let healthy = matches!(resolved, Ok(_) | Err(Error::LinkGone { .. }));
+            breaker.record(&service, healthy);
+        }
+        drop(probe);
+        let (expanded_url, scheme) = resolved?;
+        Ok(Resolution {
+            validated_url,
+            service,
+            expanded_url,
+            scheme,
+            chain: session.into_hops(),
+        })
+    }
+
+    /// `Error::ServiceDenied` for services the allow and deny lists rule out
+    fn check_lists(&self, service: &str) -> Result<()> {
+        let lists = &self.inner.services;
+        let service = service.to_string();
+        if lists.deny.contains(&service)
+            || !(lists.allow.is_empty() || lists.allow.contains(&service))
+        {
+            return Err(Error::ServiceDenied(service));
+        }
+        Ok(())
+    }
+
+    /// Whether the first response of an expired expansion is still the
+    /// same, asking the shortener with its `ETag` or `Last-Modified` date,
+    /// rather than following the whole chain again
+    async fn not_modified(&self, expanded: &ExpandedUrl) -> bool {
+        let Some(hop) = expanded.chain.first() else {
+            return false;
+        };
+        let (Ok(url), false) = (Url::parse(&hop.url), self.inner.offline) else {
+            return false;
+        };
+        let mut request = HttpRequest::new(Method::GET, url);
+        request.timeout = self
+            .inner
+            .settings
+            .service_timeouts
+            .get(&expanded.service)
+            .copied();
+        let validators = [
+            (IF_NONE_MATCH, &hop.etag),
+            (IF_MODIFIED_SINCE, &hop.last_modified),
+        ];
+        for (name, value) in validators {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                request.headers.insert(name, value);
+            }
+        }
+        if request.headers.is_empty() {
+            return false;
+        }
+        matches!(
+            self.inner.transport.send(request).await,
+            Ok(response) if response.status == StatusCode::NOT_MODIFIED
+        )
+    }
+
+    /// Status of a HEAD request to the destination (GET for servers refusing
+    /// HEAD), at the end of its redirects (to https, to `www.`...)
+    pub(crate) async fn destination_status(&self, url: &str) -> Option<u16> {
+        let mut url = Url::parse(url).ok()?;
+        let transport = &self.inner.transport;
+        let mut status = None;
+        for _ in 0..=self.inner.settings.max_redirects {
+            let head = HttpRequest::new(Method::HEAD, url.clone());
+            let mut response = transport.send(head).await.ok()?;
+            if matches!(
+                response.status,
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            ) {
+                let get = HttpRequest::new(Method::GET, url.clone());
+                response = transport.send(get).await.ok()?;
+            }
+            status = Some(response.status.as_u16());
+            if !response.status.is_redirection() {
+                break;
+            }
+            match response
+                .headers
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok())
+            {
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+        status
+    }
+
+    /// The https version of a plain http url, when its host is HSTS
+    /// preloaded or answers over https
+    async fn https_upgrade(&self, url: &str) -> Option<String> {
+        let upgraded = hsts::https_version(url)?;
+        if !upgraded.host_str().is_some_and(hsts::is_preloaded) {
+            if self.inner.offline {
+                return None;
+            }
+            let status = self.destination_status(upgraded.as_str()).await?;
+            if status >= 400 {
+                return None;
+            }
+        }
+        Some(upgraded.into())
+    }
+
+    /// Whether a domain was found to be a Bitly branded domain, when it
+    /// was probed
+    fn is_branded(&self, domain: &str) -> Option<bool> {
+        self.inner
+            .branded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(domain)
+            .map(|&(_, bitly)| bitly)
+    }
+
+    /// Remember whether a probed domain is a Bitly branded domain
+    fn set_branded(&self, domain: String, bitly: bool) {
+        let mut branded = self
+            .inner
+            .branded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !branded.contains_key(&domain) {
+            evict_oldest(&mut branded, MAX_PROBED_DOMAINS, |(probed, _)| *probed);
+        }
+        branded.insert(domain, (Instant::now(), bitly));
+    }
+
+    /// The clean url and service of a url on a Bitly branded domain, asking
+    /// the domain when it was not probed yet
+    async fn probe_branded(&self, url: &str) -> Option<(String, String)> {
+        if !self.inner.detect_branded_domains {
+            return None;
+        }
+        let parsed = parse_input(url)?;
+        let domain = ascii_domain(parsed.domain()?);
+        if self.is_branded(&domain).is_none() {
+            let head = HttpRequest::new(Method::HEAD, parsed.clone());
+            let response = self.inner.transport.send(head).await.ok()?;
+            let bitly = fingerprint::is_bitly(&response.headers);
+            self.set_branded(domain.clone(), bitly);
+        }
+        (self.is_branded(&domain) == Some(true)).then(|| (parsed.into(), BITLY.into()))
+    }
+
+    /// The clean url and service of a known or custom shortened url
+    fn service_of(&self, url: &str) -> Option<(String, String)> {
+        if let Some(validated) = validate(url) {
+            let service = which_service(&validated)?;
+            return Some((validated, service.into()));
+        }
+        let parsed = parse_input(url)?;
+        let domain = ascii_domain(parsed.domain()?);
+        let services = &self.inner.services;
+        match services
+            .custom
+            .iter()
+            .find(|&service| domain_matches_service(&domain, service))
+        {
+            Some(service) => Some((parsed.as_str().into(), service.clone())),
+            None if self.is_branded(&domain) == Some(true) => {
+                Some((parsed.as_str().into(), BITLY.into()))
+            }
+            None => services
+                .predicate
+                .as_ref()
+                .filter(|predicate| predicate(&parsed))
+                .map(|_| (parsed.as_str().into(), domain)),
+        }
+    }
+
+    /// Ask the reputation provider about the destination (and every hop after the short url)
+    async fn verdict(
+        &self,
+        provider: &dyn ReputationProvider,
+        expanded: &ExpandedUrl,
+    ) -> Result<Verdict> {
+        let mut urls = vec![expanded.url.as_str()];
+        if self.inner.check_each_hop {
+            urls.extend(
+                expanded
+                    .chain
+                    .iter()
+                    .skip(1)
+                    .map(|hop| hop.url.as_str())
+                    .filter(|&url| url != expanded.url),
+            );
+        }
+        try_join_all(urls.into_iter().map(|url| provider.check(url)))
+            .await
+            .map(|verdicts| verdicts.into_iter().max().unwrap_or(Verdict::Clean))
+    }
+}
+
+/// An expander as a tower `Service`, taking short urls, so that it can be
+/// wrapped in tower layers (timeout, retry, rate-limit...)
+#[cfg(feature = "tower")]
+impl tower_service::Service<String> for Expander {
+    type Response = ExpandedUrl;
+    type Error = Error;
+    type Future = futures::future::BoxFuture<'static, Result<ExpandedUrl>>;
+
+    fn poll_ready(&mut self, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<()>> {
+        // `max_in_flight` is enforced when expanding
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, url: String) -> Self::Future {
+        let expander = self.clone();
+        Box::pin(async move { expander.expand(&url).await })
+    }
+}
+
+/// The url with its host in Unicode form (`xn--80ak6aa92e.com` -> `аррӏе.com`)
+pub(crate) fn display_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url) else {
+        return url.into();
+    };
+    match parsed.domain() {
+        Some(domain) => format!(
+            "{}{}{}",
+            &parsed[..Position::BeforeHost],
+            idna::domain_to_unicode(domain).0,
+            &parsed[Position::AfterHost..]
+        ),
+        None => url.into(),
+    }
+}
+
+/// Add the query parameters and fragment of the short url that the expanded
+/// url does not define
+pub(crate) fn forward_query(short: &str, expanded: &str) -> String {
+    let (Ok(short), Ok(mut expanded)) = (Url::parse(short), Url::parse(expanded)) else {
+        return expanded.into();
+    };
+    let defined: Vec<String> = expanded
+        .query_pairs()
+        .map(|(k, _)| k.into_owned())
+        .collect();
+    let extra: Vec<_> = short
+        .query_pairs()
+        .filter(|(k, _)| !defined.iter().any(|d| d == k))
+        .collect();
+    if !extra.is_empty() {
+        expanded.query_pairs_mut().extend_pairs(extra);
+    }
+    if expanded.fragment().is_none() {
+        expanded.set_fragment(short.fragment());
+    }
+    expanded.into()
+}
+
+/// Record the outcome of an expansion with the `metrics` facade
+#[cfg(feature = "metrics")]
+fn record_metrics(url: &str, result: &Result<ExpandedUrl>, elapsed: Duration) {
+    let service = match result {
+        Ok(expanded) => Some(expanded.service.as_str()),
+        Err(_) => validate(url).and_then(|u| which_service(&u)),
+    };
+    // custom and branded domains are counted together, not to grow a label
+    // per domain
+    let service = service
+        .and_then(|service| {
+            crate::services::SERVICES
+                .into_iter()
+                .find(|&known| known == service)
+        })
+        .unwrap_or("other");
+    let outcome = if result.is_ok() { "success" } else { "failure" };
+    metrics::counter!("urlexpand_expansions_total", "service" => service, "outcome" => outcome)
+        .increment(1);
+    metrics::histogram!("urlexpand_expansion_duration_seconds", "service" => service)
+        .record(elapsed.as_secs_f64());
+    if let Ok(expanded) = result {
+        metrics::histogram!("urlexpand_expansion_hops", "service" => service)
+            .record(expanded.chain.len() as f64);
+    }
+}
@@ -0,0 +1,118 @@
+// Shorteners recognized from their responses
+use reqwest::header::{HeaderMap, HeaderName, SERVER, VIA};
+
+use crate::{
+    parse_input,
+    resolvers::charset,
+    transport::{HttpRequest, Method},
+    Error, Expander, Result,
+};
+
+/// Shortener software or service recognized by [`Expander::fingerprint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fingerprint {
+    /// A branded short domain of Bitly
+    Bitly,
+    /// A YOURLS install
+    Yourls,
+    /// A Polr install
+    Polr,
+    /// A Shlink install
+    Shlink,
+}
+
+/// How a shortener software tells itself in a response
+enum Sign {
+    /// This header contains this text (lowercased)
+    Header(&'static str, &'static str),
+    /// The body contains this text (lowercased)
+    Body(&'static str),
+}
+
+/// What the home pages of the self-hosted shorteners answer with
+static SIGNATURES: [(Fingerprint, Sign); 8] = [
+    (Fingerprint::Yourls, Sign::Header("x-powered-by", "yourls")),
+    (Fingerprint::Yourls, Sign::Body("powered by yourls")),
+    (Fingerprint::Yourls, Sign::Body("yourls.org")),
+    (Fingerprint::Polr, Sign::Header("x-powered-by", "polr")),
+    (Fingerprint::Polr, Sign::Body("powered by polr")),
+    (Fingerprint::Polr, Sign::Body("polrproject")),
+    (Fingerprint::Shlink, Sign::Header("x-powered-by", "shlink")),
+    (Fingerprint::Shlink, Sign::Body("shlink.io")),
+];
+
+/// Whether a header names Bitly
+fn names_bitly(headers: &HeaderMap, name: HeaderName) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.to_lowercase().contains("bitly"))
+}
+
+/// Whether a response comes from Bitly, behind one of its branded short
+/// domains: a `Via` (`1.1 bitly`) or `Server` header naming it, or headers
+/// of its own
+pub(crate) fn is_bitly(headers: &HeaderMap) -> bool {
+    names_bitly(headers, VIA)
+        || names_bitly(headers, SERVER)
+        || headers
+            .keys()
+            .any(|name| name.as_str().starts_with("x-bitly"))
+}
+
+/// The shortener software a response comes from, from its headers and body
+pub(crate) fn classify(headers: &HeaderMap, body: &str) -> Option<Fingerprint> {
+    if is_bitly(headers) {
+        return Some(Fingerprint::Bitly);
+    }
+    let body = body.to_lowercase();
+    SIGNATURES
+        .iter()
+        .find(|(_, sign)| match sign {
+            Sign::Header(name, text) => headers
+                .get_all(*name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| value.to_lowercase().contains(text)),
+            Sign::Body(text) => body.contains(text),
+        })
+        .map(|(fingerprint, _)| *fingerprint)
+}
+
+impl Expander {
+    pub async fn fingerprint<U: AsRef<str>>(&self, url: U) -> Result<Option<Fingerprint>> {
+        //! Request the home page of the domain of a url, once, and tell
+        //! whether it is likely a shortener from the response: a Bitly
+        //! branded domain, or a YOURLS, Polr or Shlink install; to crawl
+        //! unknown ecosystems, the domains found can then be added with
+        //! `ExpanderBuilder::custom_services`
+        //! ## Example
+        //! ```rust,no_run
+        //! use urlexpand::Expander;
+        //!
+        //! # #[tokio::main]
+        //! # async fn main() -> urlexpand::Result<()> {
+        //! let expander = Expander::builder().build()?;
+        //! if let Some(software) = expander.fingerprint("https://sho.rt/x").await? {
+        //!     println!("sho.rt runs {:?}", software);
+        //! }
+        //! # Ok(())
+        //! # }
+        //! ```
+        if self.inner.offline {
+            return Err(Error::NetworkRequired);
+        }
+        let home = parse_input(url.as_ref())
+            .and_then(|url| url.join("/").ok())
+            .ok_or(Error::NoString)?;
+        let response = self
+            .inner
+            .transport
+            .send(HttpRequest::new(Method::GET, home))
+            .await?;
+        let headers = response.headers.clone();
+        let body = charset::text(response).await.unwrap_or_default();
+        Ok(classify(&headers, &body))
+    }
+}
@@ -0,0 +1,436 @@
+// HTTP Archive (HAR 1.2) export and replay of the requests of expansions
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, LOCATION},
+    StatusCode,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    recording::{self, decode_body, encode_body, Recorded},
+    transport::{HttpRequest, HttpResponse, HttpTransport, Middleware, Next},
+    Error, Result,
+};
+
+/// A request made while expanding, and what became of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarEntry {
+    /// When the request was sent
+    pub started: SystemTime,
+    /// Time to the response headers
+    pub wait: Duration,
+    /// Time to read the body, after the headers
+    pub receive: Duration,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    /// Status of the response, `0` when there was none
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Why the request failed, when it did
+    pub error: Option<String>,
+}
+
+/// The requests of expansions, as an HTTP Archive that browser devtools
+/// and the other HAR tools can load
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Har {
+    pub entries: Vec<HarEntry>,
+}
+
+impl Har {
+    /// Read a HAR file, or a cassette of `testing::Recorder`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Write the archive as JSON, to a `.har` file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+
+    pub fn to_json(&self) -> String {
+        let entries: Vec<Value> = self.entries.iter().map(entry).collect();
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": {"name": "urlexpand", "version": env!("CARGO_PKG_VERSION")},
+                "entries": entries,
+            }
+        });
+        serde_json::to_string_pretty(&har).unwrap_or_default()
+    }
+
+    /// Parse a HAR, as saved by the browsers or by [`Har::save`], or the
+    /// interactions of a cassette of `testing::Recorder`
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |e: &dyn std::fmt::Display| Error::Cassette(e.to_string());
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(&e))?;
+        let entries = match (
+            value["log"]["entries"].as_array(),
+            value["interactions"].as_array(),
+        ) {
+            (Some(entries), _) => entries.iter().map(from_entry).collect(),
+            (None, Some(interactions)) => recording::interactions(interactions)
+                .map(|recorded| recorded.into_iter().map(from_recorded).collect()),
+            (None, None) => Err(invalid(&"neither log entries nor interactions")),
+        }?;
+        Ok(Self { entries })
+    }
+
+    /// The last recorded response to `url`
+    fn response(&self, url: &str) -> Option<&HarEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.url == url && entry.status != 0 && entry.error.is_none())
+    }
+}
+
+/// Replay the recorded responses, without any request: urls that were
+/// not recorded fail with `Error::NetworkRequired`, so that expansions are
+/// reproducible offline. When a url was requested more than once, its last
+/// response is replayed
+impl HttpTransport for Har {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        let mut url = request.url;
+        url.set_fragment(None);
+        let response = self.response(url.as_str()).map(|entry| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in &entry.response_headers {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) {
+                    headers.append(name, value);
+                }
+            }
+            let status =
+                StatusCode::from_u16(entry.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            HttpResponse::new(status, headers, entry.body.clone())
+        });
+        async move { response.ok_or(Error::NetworkRequired) }.boxed()
+    }
+}
+
+/// The name and value pairs of a HAR `headers` array
+fn from_headers(headers: &Value) -> Vec<(String, String)> {
+    headers
+        .as_array()
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|header| {
+                    Some((
+                        header["name"].as_str()?.into(),
+                        header["value"].as_str()?.into(),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn from_millis(millis: &Value) -> Duration {
+    Duration::try_from_secs_f64(millis.as_f64().unwrap_or_default() / 1000.0).unwrap_or_default()
+}
+
+fn from_entry(entry: &Value) -> Result<HarEntry> {
+    let (request, response) = (&entry["request"], &entry["response"]);
+    let url = request["url"]
+        .as_str()
+        .ok_or_else(|| Error::Cassette("entry without url".into()))?;
+    let content = &response["content"];
+    let body = decode_body(
+        content["text"].as_str().unwrap_or_default(),
+        content["encoding"].as_str(),
+    );
+    Ok(HarEntry {
+        started: entry["startedDateTime"]
+            .as_str()
+            .and_then(parse_iso8601)
+            .unwrap_or(UNIX_EPOCH),
+        wait: from_millis(&entry["timings"]["wait"]),
+        receive: from_millis(&entry["timings"]["receive"]),
+        method: request["method"].as_str().unwrap_or("GET").into(),
+        url: url.into(),
+        request_headers: from_headers(&request["headers"]),
+        status: response["status"]
+            .as_u64()
+            .and_then(|status| u16::try_from(status).ok())
+            .unwrap_or_default(),
+        response_headers: from_headers(&response["headers"]),
+        body,
+        error: entry["_error"].as_str().map(String::from),
+    })
+}
+
+/// An interaction of a cassette, which has no timings
+fn from_recorded(recorded: Recorded) -> HarEntry {
+    HarEntry {
+        started: UNIX_EPOCH,
+        wait: Duration::ZERO,
+        receive: Duration::ZERO,
+        method: recorded.method,
+        url: recorded.url,
+        request_headers: vec![],
+        status: recorded.status,
+        response_headers: recorded.headers,
+        body: recorded.body,
+        error: None,
+    }
+}
+
+fn headers(headers: &[(String, String)]) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect()
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> &'a str {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map_or("", |(_, value)| value)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// The HAR entry of a request
+fn entry(entry: &HarEntry) -> Value {
+    let query: Vec<Value> = url::Url::parse(&entry.url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| json!({"name": name, "value": value}))
+                .collect()
+        })
+        .unwrap_or_default();
+    let status_text = StatusCode::from_u16(entry.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or_default();
+    let (text, encoding) = encode_body(&entry.body);
+    let mut content = json!({
+        "size": entry.body.len(),
+        "mimeType": header(&entry.response_headers, CONTENT_TYPE.as_str()),
+        "text": text,
+    });
+    if let Some(encoding) = encoding {
+        content["encoding"] = encoding.into();
+    }
+    let mut value = json!({
+        "startedDateTime": iso8601(entry.started),
+        "time": millis(entry.wait + entry.receive),
+        "request": {
+            "method": entry.method,
+            "url": entry.url,
+            "httpVersion": "",
+            "cookies": [],
+            "headers": headers(&entry.request_headers),
+            "queryString": query,
+            "headersSize": -1,
+            "bodySize": 0,
+        },
+        "response": {
+            "status": entry.status,
+            "statusText": status_text,
+            "httpVersion": "",
+            "cookies": [],
+            "headers": headers(&entry.response_headers),
+            "content": content,
+            "redirectURL": header(&entry.response_headers, LOCATION.as_str()),
+            "headersSize": -1,
+            "bodySize": entry.body.len(),
+        },
+        "cache": {},
+        "timings": {
+            "send": 0,
+            "wait": millis(entry.wait),
+            "receive": millis(entry.receive),
+        },
+    });
+    if let Some(error) = &entry.error {
+        // as the browsers export the requests that failed
+        value["_error"] = error.as_str().into();
+    }
+    value
+}
+
+/// `2024-01-31T12:34:56.789Z`
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // civil date of a day count, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// The time of `2024-01-31T12:34:56.789Z`, or of the same with an offset
+/// such as `+02:00`
+fn parse_iso8601(date: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| date.get(range)?.parse::<i64>().ok();
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    let rest = date.get(19..)?;
+    let (fraction, zone) = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            (&rest[..digits], &rest[digits..])
+        }
+        None => ("", rest),
+    };
+    let nanos = format!("{fraction:0<9}").get(..9)?.parse::<u32>().ok()?;
+    let offset = match zone {
+        "Z" | "" => 0,
+        zone => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let hours = zone.get(1..3)?.parse::<i64>().ok()?;
+            let minutes = zone.get(4..6)?.parse::<i64>().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+    // day count of a civil date, after Howard Hinnant's `days_from_civil`
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    let secs = u64::try_from(secs).ok()?;
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+fn pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.into())))
+        .collect()
+}
+
+/// A middleware recording every request of the expansions into a [`Har`];
+/// clones share the recording
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{har::HarRecorder, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let recorder = HarRecorder::new();
+/// let expander = Expander::builder().middleware(recorder.clone()).build()?;
+/// expander.expand("https://bit.ly/3alqLKi").await?;
+/// recorder.har().save("session.har")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct HarRecorder {
+    entries: Arc<Mutex<Vec<HarEntry>>>,
+}
+
+impl HarRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The requests recorded so far, in the order they were sent
+    pub fn har(&self) -> Har {
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        entries.sort_by_key(|entry| entry.started);
+        Har { entries }
+    }
+
+    fn push(&self, entry: HarEntry) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(entry);
+    }
+}
+
+impl Middleware for HarRecorder {
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse>> {
+        let mut entry = HarEntry {
+            started: SystemTime::now(),
+            wait: Duration::ZERO,
+            receive: Duration::ZERO,
+            method: request.method.to_string(),
+            url: request.url.to_string(),
+            request_headers: pairs(&request.headers),
+            status: 0,
+            response_headers: vec![],
+            body: vec![],
+            error: None,
+        };
+        async move {
+            let sent = Instant::now();
+            let response = match next.run(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    entry.wait = sent.elapsed();
+                    entry.error = Some(e.to_string());
+                    self.push(entry);
+                    return Err(e);
+                }
+            };
+            entry.wait = sent.elapsed();
+            let (status, headers) = (response.status, response.headers.clone());
+            entry.status = status.as_u16();
+            entry.response_headers = pairs(&headers);
+            let body = match response.bytes().await {
+                Ok(body) => body,
+                Err(e) => {
+                    entry.receive = sent.elapsed() - entry.wait;
+                    entry.error = Some(e.to_string());
+                    self.push(entry);
+                    return Err(e);
+                }
+            };
+            entry.receive = sent.elapsed() - entry.wait;
+            entry.body = body.to_vec();
+            self.push(entry);
+            Ok(HttpResponse::new(status, headers, body))
+        }
+        .boxed()
+    }
+}
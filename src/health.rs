@@ -0,0 +1,147 @@
+// Shortener domains that went out of business
+use futures::{stream, StreamExt};
+use url::Url;
+
+use crate::{
+    resolvers::{host, Redirects, Session},
+    services::SERVICES,
+    transport::Method,
+    Error, Expander,
+};
+
+/// Domain parking services and domain marketplaces
+static PARKING_HOSTS: [&str; 15] = [
+    "above.com",
+    "afternic.com",
+    "bodis.com",
+    "buydomains.com",
+    "dan.com",
+    "domainmarket.com",
+    "domainnamesales.com",
+    "hugedomains.com",
+    "parkingcrew.net",
+    "parklogic.com",
+    "sav.com",
+    "sedo.com",
+    "sedoparking.com",
+    "undeveloped.com",
+    "uniregistry.com",
+];
+
+/// Phrases of parking pages and domain-for-sale landers, lowercased
+static PARKING_MARKERS: [&str; 8] = [
+    "this domain is for sale",
+    "this domain may be for sale",
+    "buy this domain",
+    "domain is parked",
+    "parked free, courtesy of",
+    "is available for purchase",
+    "sedoparking",
+    "parkingcrew",
+];
+
+/// Whether the url is on a domain parking service or marketplace
+pub(crate) fn is_parking_url(url: &str) -> bool {
+    let Some(host) = Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+    else {
+        return false;
+    };
+    PARKING_HOSTS.iter().any(|&parking| {
+        host == parking
+            || host
+                .strip_suffix(parking)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// Whether a page is a parking page or a domain-for-sale lander
+pub(crate) fn is_parking_page(html: &str) -> bool {
+    let html = html.to_lowercase();
+    PARKING_MARKERS.iter().any(|marker| html.contains(marker))
+}
+
+/// How a shortener service is doing
+#[derive(Debug, PartialEq)]
+pub enum ServiceStatus {
+    /// The service answered, with this status
+    Up(u16),
+    /// The domain is parked or for sale
+    Defunct,
+    /// The service could not be reached
+    Down(Error),
+}
+
+/// How a shortener service is doing, from [`Expander::check_services`]
+#[derive(Debug, PartialEq)]
+pub struct ServiceHealth {
+    pub service: String,
+    pub status: ServiceStatus,
+}
+
+impl Expander {
+    pub async fn check_services(&self, concurrency: usize) -> Vec<ServiceHealth> {
+        //! Request the home page of every known (and custom) shortener,
+        //! `concurrency` at a time, telling the services that are up from
+        //! the ones that are down and the ones whose domain is now parked
+        //! or for sale
+        //! ## Example
+        //! ```rust,no_run
+        //! use urlexpand::{Expander, ServiceStatus};
+        //!
+        //! # #[tokio::main]
+        //! # async fn main() -> urlexpand::Result<()> {
+        //! let expander = Expander::builder().build()?;
+        //! for health in expander.check_services(16).await {
+        //!     if health.status != ServiceStatus::Up(200) {
+        //!         println!("{}: {:?}", health.service, health.status);
+        //!     }
+        //! }
+        //! # Ok(())
+        //! # }
+        //! ```
+        let services = SERVICES
+            .iter()
+            .map(|&service| service.to_string())
+            .chain(self.inner.services.custom.iter().cloned());
+        stream::iter(services)
+            .map(|service| async move {
+                let status = self.service_status(&service).await;
+                ServiceHealth { service, status }
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Whether the home page of the shortener of a url is parked
+    pub(crate) async fn home_parked(&self, url: &str) -> bool {
+        let Some(shortener) = host(url) else {
+            return false;
+        };
+        matches!(
+            self.service_status(&shortener).await,
+            ServiceStatus::Defunct
+        )
+    }
+
+    /// Whether the home page of a service is up, and not a parking page
+    async fn service_status(&self, service: &str) -> ServiceStatus {
+        let session = Session::new(self.inner.transport.as_ref(), &self.inner.settings);
+        let home = format!("https://{}/", service);
+        let reply = match session.send(Method::GET, &home, Redirects::All).await {
+            Ok(reply) => reply,
+            Err(e) => return ServiceStatus::Down(e),
+        };
+        if is_parking_url(reply.url.as_str()) {
+            return ServiceStatus::Defunct;
+        }
+        let status = reply.response.status.as_u16();
+        match session.text(reply.response).await {
+            Ok(html) if is_parking_page(&html) => ServiceStatus::Defunct,
+            Ok(_) => ServiceStatus::Up(status),
+            Err(e) => ServiceStatus::Down(e),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// Offline phishing heuristics for expanded URLs
+use url::{Host, Url};
+
+/// Top level domains disproportionately used for phishing
+static SUSPICIOUS_TLDS: [&str; 16] = [
+    "cf", "click", "country", "ga", "gq", "kim", "link", "ml", "mov", "rest", "top", "tk", "work",
+    "xyz", "zip", "support",
+];
+
+/// Hosts with more labels than this are considered to have excessive subdomains
+static MAX_LABELS: usize = 4;
+
+/// Something about a URL that is commonly seen in phishing links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reason {
+    /// A label of the host is punycode encoded (possible homoglyph attack)
+    Punycode,
+    /// The host has an unusual number of subdomains
+    ExcessiveSubdomains,
+    /// The host is an IP address instead of a domain
+    IpAddressHost,
+    /// The url carries a username or password (`https://paypal.com@evil.com`)
+    Credentials,
+    /// The host is under a top level domain often abused for phishing
+    SuspiciousTld,
+}
+
+impl Reason {
+    /// Weight of the reason in the overall score
+    fn weight(&self) -> u8 {
+        match self {
+            Reason::Punycode => 30,
+            Reason::ExcessiveSubdomains => 15,
+            Reason::IpAddressHost => 25,
+            Reason::Credentials => 30,
+            Reason::SuspiciousTld => 15,
+        }
+    }
+}
+
+/// Phishing likelihood of a URL: 0 (nothing found) to 100
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RiskScore {
+    pub score: u8,
+    pub reasons: Vec<Reason>,
+}
+
+impl RiskScore {
+    fn from_reasons(reasons: Vec<Reason>) -> Self {
+        let score = reasons.iter().map(Reason::weight).sum::<u8>().min(100);
+        Self { score, reasons }
+    }
+}
+
+pub fn score(url: &str) -> RiskScore {
+    //! Score a URL with the offline phishing heuristics
+    //! ## Example
+    //! ```rust
+    //! use urlexpand::heuristics::{score, Reason};
+    //!
+    //! let risk = score("http://paypal.com@192.168.10.1/login");
+    //! assert!(risk.reasons.contains(&Reason::Credentials));
+    //! assert!(risk.reasons.contains(&Reason::IpAddressHost));
+    //! ```
+    let parsed = match Url::parse(url) {
+        Ok(u) => u,
+        Err(_) => return RiskScore::default(),
+    };
+    let mut reasons = vec![];
+    match parsed.host() {
+        Some(Host::Domain(domain)) => {
+            let labels: Vec<&str> = domain.trim_end_matches('.').split('.').collect();
+            if labels.iter().any(|l| l.starts_with("xn--")) {
+                reasons.push(Reason::Punycode);
+            }
+            if labels.len() > MAX_LABELS {
+                reasons.push(Reason::ExcessiveSubdomains);
+            }
+            if labels
+                .last()
+                .map(|tld| SUSPICIOUS_TLDS.contains(&tld.to_lowercase().as_str()))
+                .unwrap_or(false)
+            {
+                reasons.push(Reason::SuspiciousTld);
+            }
+        }
+        Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)) => reasons.push(Reason::IpAddressHost),
+        None => {}
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        reasons.push(Reason::Credentials);
+    }
+    RiskScore::from_reasons(reasons)
+}
@@ -0,0 +1,51 @@
+// Hosts known to be served over https
+use url::Url;
+
+/// Snapshot of the HSTS preload list: domains preloaded with their
+/// subdomains, and top-level domains that are https only
+static PRELOADED: [&str; 22] = [
+    "1password.com",
+    "app",
+    "bank",
+    "day",
+    "dev",
+    "dropbox.com",
+    "esq",
+    "facebook.com",
+    "foo",
+    "github.com",
+    "insurance",
+    "mov",
+    "new",
+    "page",
+    "paypal.com",
+    "prof",
+    "stripe.com",
+    "torproject.org",
+    "twitter.com",
+    "wikipedia.org",
+    "x.com",
+    "zip",
+];
+
+/// Whether the host (or one of its parent domains) is on the HSTS preload
+/// list, so that browsers never reach it over plain http
+pub(crate) fn is_preloaded(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_lowercase();
+    PRELOADED.iter().any(|&preloaded| {
+        host == preloaded
+            || host
+                .strip_suffix(preloaded)
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+/// The https version of a plain http url on the default port
+pub(crate) fn https_version(url: &str) -> Option<Url> {
+    let mut url = Url::parse(url).ok()?;
+    if url.scheme() != "http" || url.port().is_some() {
+        return None;
+    }
+    url.set_scheme("https").ok()?;
+    Some(url)
+}
@@ -1,18 +1,82 @@
+#[cfg(feature = "network")]
 use std::time::Duration;
 use url::{ParseError, Url};
 
+#[cfg(feature = "network")]
+mod batch;
+#[cfg(feature = "network")]
+mod breaker;
+#[cfg(feature = "network")]
+mod cache;
+#[cfg(feature = "network")]
+mod config;
+mod decoders;
+#[cfg(feature = "network")]
+pub mod dns;
+#[cfg(feature = "network")]
+pub mod documents;
 mod error;
+#[cfg(feature = "network")]
+mod expander;
+#[cfg(feature = "network")]
+mod fingerprint;
+#[cfg(feature = "har")]
+pub mod har;
+#[cfg(feature = "network")]
+mod health;
+pub mod heuristics;
+#[cfg(feature = "network")]
+mod hsts;
+mod normalize;
+pub mod parsers;
+#[cfg(any(feature = "har", feature = "testing"))]
+mod recording;
+#[cfg(feature = "network")]
+pub mod reputation;
 mod resolvers;
 
 mod services;
-use services::{which_service, SERVICES};
-
-#[cfg(test)]
+#[cfg(feature = "network")]
+mod suggest;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "network")]
+pub mod transport;
+use services::{ascii_domain, which_service, SERVICES};
+
+#[cfg(all(test, feature = "network"))]
 mod tests;
 
 pub type Error = error::Error;
+pub use error::TimeoutStage;
 pub type Result<T> = std::result::Result<T, Error>;
 
+#[cfg(feature = "network")]
+pub use batch::{
+    unshorten_from_reader, Async, BatchItem, BatchOptions, BatchReport, ExpandUrls, UrlExpandExt,
+};
+#[cfg(feature = "blocking")]
+pub use batch::{unshorten_many_blocking, Blocking, ExpandUrlsBlocking};
+#[cfg(feature = "network")]
+pub use config::{set_default_config, Config, Options};
+#[cfg(feature = "config-file")]
+pub use config::{CacheConfig, ExpanderConfig, ServicesConfig};
+#[cfg(feature = "network")]
+pub use expander::{
+    ContentEncoding, ExpandedUrl, Expander, ExpanderBuilder, HopInfo, HopTiming, HttpVersion,
+    RedirectAction,
+};
+#[cfg(feature = "network")]
+pub use fingerprint::Fingerprint;
+#[cfg(feature = "network")]
+pub use health::{ServiceHealth, ServiceStatus};
+pub use normalize::{strip_tracking, Normalization};
+#[cfg(feature = "network")]
+pub use reputation::{ReputationProvider, Verdict};
+#[cfg(feature = "network")]
+pub use suggest::SuggestedService;
+
+#[cfg(feature = "network")]
 use futures::future::{ready, TryFutureExt};
 
 /// Check if domain matches a shortener service (exact match or subdomain)
@@ -26,20 +90,22 @@ fn domain_matches_service(domain: &str, service: &str) -> bool {
 
 /// Check if a domain (without scheme) is a shortened URL service
 fn domain_is_shortened(domain: &str) -> bool {
-    let d = domain.to_lowercase();
-    let d = d.strip_suffix('.').unwrap_or(&d);
-    SERVICES.iter().any(|&svc| domain_matches_service(d, svc))
+    let d = ascii_domain(domain);
+    SERVICES.iter().any(|&svc| domain_matches_service(&d, svc))
 }
 
-pub fn is_shortened(url: &str) -> bool {
-    //! Check to see if a given url is a shortened url
+pub fn is_shortened<U: AsRef<str>>(url: U) -> bool {
+    //! Check to see if a given url (`&str`, `String` or `Url`) is a shortened url
     //! ## Example
     //! ```rust
+    //! use url::Url;
     //! use urlexpand::is_shortened;
     //!
     //! let url = "https://bit.ly/id";
     //! assert!(is_shortened(url));
+    //! assert!(is_shortened(Url::parse(url).unwrap()));
     //! ```
+    let url = url.as_ref();
     Url::parse(url)
         .or_else(|_| Url::parse(&format!("https://{}", url)))
         .ok()
@@ -48,8 +114,21 @@ pub fn is_shortened(url: &str) -> bool {
         .unwrap_or(false)
 }
 
+pub fn shortener_service<U: AsRef<str>>(url: U) -> Option<&'static str> {
+    //! The shortener service of a url (`&str`, `String` or `Url`), when it is
+    //! a shortened url; no request is made
+    //! ## Example
+    //! ```rust
+    //! use urlexpand::shortener_service;
+    //!
+    //! assert_eq!(shortener_service("https://www.bit.ly/id"), Some("bit.ly"));
+    //! assert_eq!(shortener_service("https://example.com/"), None);
+    //! ```
+    which_service(url.as_ref())
+}
+
 #[cfg(feature = "blocking")]
-pub fn unshorten_blocking(url: &str, timeout: Option<Duration>) -> Result<String> {
+pub fn unshorten_blocking<U: AsRef<str>>(url: U, timeout: Option<Duration>) -> Result<String> {
     //! UnShorten a shortened URL
     //! ## Example
     //! ```ignore
@@ -64,8 +143,9 @@ pub fn unshorten_blocking(url: &str, timeout: Option<Duration>) -> Result<String
     rt.block_on(unshorten(url, timeout))
 }
 
-pub async fn unshorten(url: &str, timeout: Option<Duration>) -> Result<String> {
-    //! UnShorten a shortened URL
+#[cfg(feature = "network")]
+pub async fn unshorten<U: AsRef<str>>(url: U, timeout: Option<Duration>) -> Result<String> {
+    //! UnShorten a shortened URL, given as `&str`, `String` or `Url`
     //! ## Example
     //! ```ignore
     //!  use std::time::Duration;
@@ -75,44 +155,43 @@ pub async fn unshorten(url: &str, timeout: Option<Duration>) -> Result<String> {
     //!  assert!(unshorten(url, Some(Duration::from_secs(10))).await.is_ok());   // with timeout
     //!  assert!(unshorten(url, None).await.is_ok());    // without timeout
     //! ```
-    // Check to make sure url is valid
-    ready(validate(url).ok_or(Error::NoString))
-        .and_then(|validated_url| async move {
-            let service = which_service(&validated_url).ok_or(Error::NoString)?;
-
-            match service {
-                // Adfly Resolver
-                "adf.ly" | "atominik.com" | "fumacrom.com" | "intamema.com" | "j.gs" | "q.gs" => {
-                    resolvers::adfly::unshort(&validated_url, timeout).await
-                }
-
-                // Redirect Resolvers (JavaScript-based redirects)
-                "gns.io" | "ity.im" | "ldn.im" | "nowlinks.net" | "rlu.ru" | "tinyurl.com"
-                | "tr.im" | "vzturl.com" => {
-                    resolvers::redirect::unshort(&validated_url, timeout).await
-                }
-
-                // HTTP 3xx Redirect Resolvers
-                "u.to" => resolvers::http_redirect::unshort(&validated_url, timeout).await,
-
-                // Meta Refresh Resolvers
-                "cutt.us" | "soo.gd" => resolvers::refresh::unshort(&validated_url, timeout).await,
-
-                // Specific Resolvers
-                "adfoc.us" => resolvers::adfocus::unshort(&validated_url, timeout).await,
-                "lnkd.in" => resolvers::linkedin::unshort(&validated_url, timeout).await,
-                "shorturl.at" => resolvers::shorturl::unshort(&validated_url, timeout).await,
-                "surl.li" => resolvers::surlli::unshort(&validated_url, timeout).await,
+    let options = Options {
+        timeout,
+        ..Default::default()
+    };
+    unshorten_with(url, &options).await
+}
 
-                // Generic Resolvers
-                _ => resolvers::generic::unshort(&validated_url, timeout).await,
-            }
-        })
+#[cfg(feature = "network")]
+pub async fn unshorten_with<U: AsRef<str>>(url: U, options: &Options) -> Result<String> {
+    //! UnShorten a shortened URL with a few settings of its own, on top of
+    //! the ones set with [`set_default_config`]
+    //! ## Example
+    //! ```ignore
+    //!  use urlexpand::{unshorten_with, Options};
+    //!
+    //!  let options = Options {
+    //!      max_redirects: Some(3),
+    //!      user_agent: Some("my-crawler/1.0".into()),
+    //!      refuse_downgrades: true,
+    //!      ..Default::default()
+    //!  };
+    //!  assert!(unshorten_with("https://bit.ly/3alqLKi", &options).await.is_ok());
+    //! ```
+    ready(options.builder().build())
+        .and_then(|expander| async move { expander.expand(url.as_ref()).await })
+        .map_ok(|expanded| expanded.url)
         .await
 }
 
-/// Validate & return a clean URL
-fn validate(u: &str) -> Option<String> {
+/// Check if the url was given with a scheme (`validate` assumes https otherwise)
+#[cfg(feature = "network")]
+fn has_scheme(u: &str) -> bool {
+    !matches!(Url::parse(u), Err(ParseError::RelativeUrlWithoutBase))
+}
+
+/// Parse a url, assuming https when it has no scheme
+fn parse_input(u: &str) -> Option<Url> {
     let parts = match Url::parse(u) {
         Ok(p) => p,
         Err(e) => match e {
@@ -126,9 +205,30 @@ fn validate(u: &str) -> Option<String> {
             _ => return None,
         },
     };
+    Some(parts)
+}
 
+/// Validate & return a clean URL
+#[cfg(feature = "network")]
+fn validate(u: &str) -> Option<String> {
+    normalize_short_url(u).map(String::from)
+}
+
+pub fn normalize_short_url(url: &str) -> Option<Url> {
+    //! Validate & canonicalize a short url with the rules of the expander:
+    //! https is assumed when there is no scheme, the host is lowercased (and
+    //! IDNs converted to punycode), and the domain must be a known shortener
+    //! ## Example
+    //! ```rust
+    //! use urlexpand::normalize_short_url;
+    //!
+    //! let url = normalize_short_url("BIT.ly/3alqLKi").unwrap();
+    //! assert_eq!(url.as_str(), "https://bit.ly/3alqLKi");
+    //! assert!(normalize_short_url("https://example.com/").is_none());
+    //! ```
+    let parts = parse_input(url)?;
     parts
         .domain()
-        .filter(|d| domain_is_shortened(d))
-        .map(|_| parts.as_str().into())
+        .is_some_and(domain_is_shortened)
+        .then_some(parts)
 }
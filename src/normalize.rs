@@ -0,0 +1,253 @@
+// Normalization of expanded URLs, so that they can be used as dedup keys
+
+/// Which normalizations to apply to an expanded url; all are off by default.
+///
+/// Urls extracted from page content (meta refresh, JavaScript redirects) are
+/// returned as written on the page, so two expansions of the same
+/// destination can differ in case, ports or escaping without these.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// `HTTPS://Example.COM/` -> `https://example.com/` (scheme included)
+    pub lowercase_host: bool,
+    /// `https://example.com:443/` -> `https://example.com/`
+    pub remove_default_port: bool,
+    /// `/a/./b/../c` -> `/a/c`
+    pub collapse_dot_segments: bool,
+    /// `?b=2&a=1` -> `?a=1&b=2`
+    pub sort_query: bool,
+    /// `/%7Euser` -> `/~user` (only for letters, digits and `-._~`)
+    pub decode_unreserved: bool,
+}
+
+impl Normalization {
+    /// Every normalization
+    pub fn all() -> Self {
+        Self {
+            lowercase_host: true,
+            remove_default_port: true,
+            collapse_dot_segments: true,
+            sort_query: true,
+            decode_unreserved: true,
+        }
+    }
+
+    /// Apply the normalizations to a url; anything that does not look like
+    /// an absolute url is returned unchanged
+    /// ## Example
+    /// ```rust
+    /// use urlexpand::Normalization;
+    ///
+    /// let url = "HTTPS://Example.COM:443/a/../%7Eb?z=1&a=2";
+    /// assert_eq!(Normalization::all().apply(url), "https://example.com/~b?a=2&z=1");
+    /// ```
+    pub fn apply(&self, url: &str) -> String {
+        let Some((scheme, rest)) = url.split_once("://") else {
+            return url.into();
+        };
+        let (rest, fragment) = split_off(rest, '#');
+        let (rest, query) = split_off(rest, '?');
+        let authority_end = rest.find('/').unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+
+        let scheme = if self.lowercase_host {
+            scheme.to_lowercase()
+        } else {
+            scheme.into()
+        };
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = split_port(host_port);
+        let host = if self.lowercase_host {
+            host.to_lowercase()
+        } else {
+            host.into()
+        };
+        let port =
+            port.filter(|&port| !(self.remove_default_port && default_port(&scheme) == Some(port)));
+        let mut path = if self.collapse_dot_segments {
+            remove_dot_segments(path)
+        } else {
+            path.into()
+        };
+        let mut query = query.map(|query| {
+            if self.sort_query {
+                let mut pairs: Vec<&str> = query.split('&').collect();
+                pairs.sort();
+                pairs.join("&")
+            } else {
+                query.into()
+            }
+        });
+        let mut fragment = fragment.map(String::from);
+        if self.decode_unreserved {
+            path = decode_unreserved(&path);
+            query = query.as_deref().map(decode_unreserved);
+            fragment = fragment.as_deref().map(decode_unreserved);
+        }
+
+        let mut normalized = format!("{}://", scheme);
+        if let Some(userinfo) = userinfo {
+            normalized.push_str(userinfo);
+            normalized.push('@');
+        }
+        normalized.push_str(&host);
+        if let Some(port) = port {
+            normalized.push(':');
+            normalized.push_str(port);
+        }
+        normalized.push_str(&path);
+        if let Some(query) = query {
+            normalized.push('?');
+            normalized.push_str(&query);
+        }
+        if let Some(fragment) = fragment {
+            normalized.push('#');
+            normalized.push_str(&fragment);
+        }
+        normalized
+    }
+}
+
+/// Query parameters that only track where a click came from
+static TRACKING_PARAMS: [&str; 20] = [
+    "_hsenc",
+    "_hsmi",
+    "dclid",
+    "fbclid",
+    "gbraid",
+    "gclid",
+    "gclsrc",
+    "igsh",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "mkt_tok",
+    "msclkid",
+    "oly_anon_id",
+    "oly_enc_id",
+    "ref_src",
+    "twclid",
+    "vero_id",
+    "wbraid",
+    "yclid",
+];
+
+/// Remove the tracking parameters (`utm_*`, `fbclid`, `gclid`...) from the
+/// query of a url, leaving the other parameters as they are written
+/// ## Example
+/// ```rust
+/// use urlexpand::strip_tracking;
+///
+/// let url = "https://example.com/a?id=1&utm_source=x&fbclid=y#top";
+/// assert_eq!(strip_tracking(url), "https://example.com/a?id=1#top");
+/// ```
+pub fn strip_tracking(url: &str) -> String {
+    let (rest, fragment) = split_off(url, '#');
+    let (base, query) = split_off(rest, '?');
+    let Some(query) = query else {
+        return url.into();
+    };
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default().to_lowercase();
+            !(name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str()))
+        })
+        .collect();
+    let mut stripped = String::from(base);
+    if !kept.is_empty() {
+        stripped.push('?');
+        stripped.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        stripped.push('#');
+        stripped.push_str(fragment);
+    }
+    stripped
+}
+
+/// Split a string at the first `delimiter`, dropping it
+fn split_off(s: &str, delimiter: char) -> (&str, Option<&str>) {
+    match s.split_once(delimiter) {
+        Some((before, after)) => (before, Some(after)),
+        None => (s, None),
+    }
+}
+
+/// Split `host:port`, minding the colons of IPv6 literals
+fn split_port(host_port: &str) -> (&str, Option<&str>) {
+    let host_end = if host_port.starts_with('[') {
+        host_port
+            .find(']')
+            .map(|i| i + 1)
+            .unwrap_or(host_port.len())
+    } else {
+        host_port.find(':').unwrap_or(host_port.len())
+    };
+    let (host, port) = host_port.split_at(host_end);
+    (host, port.strip_prefix(':'))
+}
+
+fn default_port(scheme: &str) -> Option<&'static str> {
+    match scheme {
+        "http" | "ws" => Some("80"),
+        "https" | "wss" => Some("443"),
+        "ftp" => Some("21"),
+        _ => None,
+    }
+}
+
+/// `remove_dot_segments` of RFC 3986, section 5.2.4
+fn remove_dot_segments(path: &str) -> String {
+    let mut output: Vec<&str> = vec![];
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    for (i, &segment) in segments.iter().enumerate() {
+        let last = i + 1 == segments.len();
+        match segment {
+            "." if last => output.push(""),
+            "." => {}
+            ".." => {
+                output.pop();
+                // a trailing dot segment still denotes a directory
+                if last {
+                    output.push("");
+                }
+            }
+            _ => output.push(segment),
+        }
+    }
+    if path.starts_with('/') {
+        format!("/{}", output.join("/"))
+    } else {
+        path.into()
+    }
+}
+
+/// Decode the percent-escapes of unreserved characters, leaving the others alone
+fn decode_unreserved(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if let [b'%', hi, lo, ..] = bytes[i..] {
+            if let (Some(hi), Some(lo)) = (hex_value(hi), hex_value(lo)) {
+                let byte = hi << 4 | lo;
+                if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                    decoded.push(byte as char);
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+        let ch = s[i..].chars().next().unwrap_or_default();
+        decoded.push(ch);
+        i += ch.len_utf8();
+    }
+    decoded
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
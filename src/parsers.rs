@@ -0,0 +1,72 @@
+// Pure extractors used by the resolvers, for reuse and fuzzing
+use crate::{decoders, parse_input, resolvers};
+
+/// The target of a `<meta http-equiv="refresh">` tag
+/// ## Example
+/// ```rust
+/// use urlexpand::parsers::meta_refresh;
+///
+/// let html = r#"<meta http-equiv="refresh" content="0; URL=https://example.com/">"#;
+/// assert_eq!(meta_refresh(html), Some("https://example.com/".into()));
+/// ```
+pub fn meta_refresh(html: &str) -> Option<String> {
+    resolvers::refresh::from_page(html)
+}
+
+/// The target of a JavaScript redirect (`window.location`, `window.open`)
+/// or of one of the interstitial pages of the supported shorteners
+/// ## Example
+/// ```rust
+/// use urlexpand::parsers::js_location;
+///
+/// let html = r#"<script>window.location = "https://example.com/";</script>"#;
+/// assert_eq!(js_location(html), Some("https://example.com/".into()));
+/// ```
+pub fn js_location(html: &str) -> Option<String> {
+    resolvers::redirect::from_page(html)
+}
+
+/// The destination hidden in the `ysmm` variable of an adf.ly page
+pub fn adfly_ysmm(ysmm: &str) -> Option<String> {
+    resolvers::adfly::decode_ysmm(ysmm)
+}
+
+/// The destination of an adf.ly interstitial page, from its `ysmm` value
+/// (script variable, data attribute or JSON config)
+pub fn adfly_page(html: &str) -> Option<String> {
+    resolvers::adfly::from_page(html)
+}
+
+/// The destination of a link wrapper that carries it in its url (Google and
+/// Facebook redirects, SafeLinks, urldefense, WhatsApp, YouTube...), with
+/// the name of the wrapper
+/// ## Example
+/// ```rust
+/// use urlexpand::parsers::unwrap_link;
+///
+/// let url = "https://www.google.com/url?q=https://example.com/&sa=D";
+/// assert_eq!(unwrap_link(url), Some(("google.com", "https://example.com/".into())));
+/// ```
+pub fn unwrap_link(url: &str) -> Option<(&'static str, String)> {
+    decoders::decode(url)
+}
+
+/// The destination of a Microsoft Defender SafeLinks url
+/// ## Example
+/// ```rust
+/// use urlexpand::parsers::safelinks;
+///
+/// let url = "https://eur01.safelinks.protection.outlook.com/?url=https%3A%2F%2Fexample.com%2F&data=1";
+/// assert_eq!(safelinks(url), Some("https://example.com/".into()));
+/// ```
+pub fn safelinks(url: &str) -> Option<String> {
+    let url = parse_input(url)?;
+    if !url
+        .host_str()?
+        .to_lowercase()
+        .ends_with("safelinks.protection.outlook.com")
+    {
+        return None;
+    }
+    decoders::safelinks(&url)
+}
@@ -0,0 +1,66 @@
+// Recorded interactions in JSON, shared by cassettes and HAR files
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::Value;
+
+use crate::{Error, Result};
+
+/// A request and the response it got, as a cassette saves it
+pub(crate) struct Recorded {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// The `interactions` of a cassette, each `{"request": {...}, "response": {...}}`
+pub(crate) fn interactions(interactions: &[Value]) -> Result<Vec<Recorded>> {
+    interactions.iter().map(interaction).collect()
+}
+
+fn interaction(interaction: &Value) -> Result<Recorded> {
+    let (request, response) = (&interaction["request"], &interaction["response"]);
+    let url = request["url"]
+        .as_str()
+        .ok_or_else(|| Error::Cassette("interaction without url".into()))?;
+    let status = response["status"]
+        .as_u64()
+        .and_then(|status| u16::try_from(status).ok())
+        .ok_or_else(|| Error::Cassette("interaction without status".into()))?;
+    let headers = response["headers"]
+        .as_array()
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|header| Some((header[0].as_str()?.into(), header[1].as_str()?.into())))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Recorded {
+        method: request["method"].as_str().unwrap_or("GET").into(),
+        url: url.into(),
+        status,
+        headers,
+        body: decode_body(
+            response["body"].as_str().unwrap_or_default(),
+            response["encoding"].as_str(),
+        ),
+    })
+}
+
+/// The text of a body and its encoding: UTF-8 bodies are kept as they
+/// are, others in base64 with the `base64` encoding, as HAR does
+pub(crate) fn encode_body(body: &[u8]) -> (String, Option<&'static str>) {
+    match std::str::from_utf8(body) {
+        Ok(text) => (text.into(), None),
+        Err(_) => (general_purpose::STANDARD.encode(body), Some("base64")),
+    }
+}
+
+/// The bytes of a body written by [`encode_body`]
+pub(crate) fn decode_body(text: &str, encoding: Option<&str>) -> Vec<u8> {
+    match encoding {
+        Some("base64") => general_purpose::STANDARD.decode(text).unwrap_or_default(),
+        _ => text.into(),
+    }
+}
@@ -0,0 +1,64 @@
+// Reputation checks of expanded URLs
+use futures::future::BoxFuture;
+
+use crate::Result;
+
+#[cfg(feature = "safe-browsing")]
+pub(crate) mod safe_browsing;
+#[cfg(feature = "safe-browsing")]
+pub use safe_browsing::SafeBrowsing;
+#[cfg(feature = "urlscan")]
+pub(crate) mod urlscan;
+#[cfg(feature = "urlscan")]
+pub use urlscan::{Urlscan, Visibility};
+#[cfg(feature = "virustotal")]
+pub(crate) mod virustotal;
+#[cfg(feature = "virustotal")]
+pub use virustotal::VirusTotal;
+
+/// Reputation of a URL, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Verdict {
+    Clean,
+    Suspicious,
+    Malicious,
+}
+
+/// A service that can tell if a URL is safe to visit
+pub trait ReputationProvider: Send + Sync {
+    /// Return the verdict for the given url
+    fn check<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Verdict>>;
+}
+
+/// Number of engines that flagged a URL, as reported by VirusTotal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detections {
+    pub malicious: u32,
+    pub suspicious: u32,
+    pub harmless: u32,
+    pub undetected: u32,
+}
+
+impl Detections {
+    /// Verdict implied by the detection counts
+    pub fn verdict(&self) -> Verdict {
+        if self.malicious > 0 {
+            Verdict::Malicious
+        } else if self.suspicious > 0 {
+            Verdict::Suspicious
+        } else {
+            Verdict::Clean
+        }
+    }
+}
+
+/// A scan submitted to urlscan.io
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanSubmission {
+    pub uuid: String,
+    /// Where the scan report will be available once the scan completes
+    pub report_url: String,
+}
@@ -0,0 +1,106 @@
+// Google Safe Browsing (v4 Lookup API) reputation provider
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{ReputationProvider, Verdict};
+use crate::{resolvers::verified_client, Error, Result};
+
+static ENDPOINT: &str = "https://safebrowsing.googleapis.com/v4/threatMatches:find";
+
+/// Threat types that make a URL malicious; any other match is suspicious
+static MALICIOUS_THREATS: [&str; 2] = ["MALWARE", "SOCIAL_ENGINEERING"];
+
+/// Checks URLs against the Google Safe Browsing lists
+pub struct SafeBrowsing {
+    api_key: String,
+    client_id: String,
+    timeout: Option<Duration>,
+    /// Shared by the lookups, `Err` when it could not be built
+    pub(crate) client: reqwest::Result<Client>,
+}
+
+impl SafeBrowsing {
+    /// Create a provider using the given Google API key
+    pub fn new<S: Into<String>>(api_key: S) -> Self {
+        Self {
+            api_key: api_key.into(),
+            client_id: env!("CARGO_PKG_NAME").into(),
+            timeout: None,
+            client: verified_client(),
+        }
+    }
+
+    /// Client id reported to the Safe Browsing API
+    pub fn client_id<S: Into<String>>(mut self, client_id: S) -> Self {
+        self.client_id = client_id.into();
+        self
+    }
+
+    /// Timeout of the lookup request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    async fn lookup(&self, url: &str) -> Result<Verdict> {
+        let body = json!({
+            "client": {
+                "clientId": self.client_id,
+                "clientVersion": env!("CARGO_PKG_VERSION"),
+            },
+            "threatInfo": {
+                "threatTypes": [
+                    "MALWARE",
+                    "SOCIAL_ENGINEERING",
+                    "UNWANTED_SOFTWARE",
+                    "POTENTIALLY_HARMFUL_APPLICATION",
+                ],
+                "platformTypes": ["ANY_PLATFORM"],
+                "threatEntryTypes": ["URL"],
+                "threatEntries": [{ "url": url }],
+            },
+        });
+        let client = self
+            .client
+            .as_ref()
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+        let mut request = client
+            .post(ENDPOINT)
+            .query(&[("key", &self.api_key)])
+            .json(&body);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::Reputation(format!(
+                "safe browsing returned {}",
+                response.status()
+            )));
+        }
+        Ok(verdict_from_response(&response.json().await?))
+    }
+}
+
+impl ReputationProvider for SafeBrowsing {
+    fn check<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Verdict>> {
+        Box::pin(self.lookup(url))
+    }
+}
+
+/// Map a `threatMatches:find` response to a Verdict
+pub(crate) fn verdict_from_response(response: &Value) -> Verdict {
+    response["matches"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|m| match m["threatType"].as_str() {
+            Some(threat) if MALICIOUS_THREATS.contains(&threat) => Verdict::Malicious,
+            _ => Verdict::Suspicious,
+        })
+        .max()
+        .unwrap_or(Verdict::Clean)
+}
@@ -0,0 +1,105 @@
+// urlscan.io scan submissions
+use std::time::Duration;
+
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::ScanSubmission;
+use crate::{resolvers::verified_client, Error, Result};
+
+static ENDPOINT: &str = "https://urlscan.io/api/v1/scan/";
+
+/// Visibility of a submitted scan on urlscan.io
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Unlisted,
+    Private,
+}
+
+impl Visibility {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+}
+
+/// Submits URLs to urlscan.io for scanning
+pub struct Urlscan {
+    api_key: String,
+    visibility: Visibility,
+    tags: Vec<String>,
+    timeout: Option<Duration>,
+    /// Shared by the submissions, `Err` when it could not be built
+    pub(crate) client: reqwest::Result<Client>,
+}
+
+impl Urlscan {
+    /// Create a submitter using the given urlscan.io API key
+    pub fn new<S: Into<String>>(api_key: S) -> Self {
+        Self {
+            api_key: api_key.into(),
+            visibility: Visibility::Unlisted,
+            tags: vec![],
+            timeout: None,
+            client: verified_client(),
+        }
+    }
+
+    /// Visibility of the scans (default: unlisted)
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Tag attached to every submitted scan
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Timeout of the submission request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Submit the url; the scan itself completes asynchronously on urlscan.io
+    pub async fn submit(&self, url: &str) -> Result<ScanSubmission> {
+        let client = self
+            .client
+            .as_ref()
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+        let mut request = client
+            .post(ENDPOINT)
+            .header("API-Key", &self.api_key)
+            .json(&json!({
+                "url": url,
+                "visibility": self.visibility.as_str(),
+                "tags": self.tags,
+            }));
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::Reputation(format!(
+                "urlscan.io returned {}",
+                response.status()
+            )));
+        }
+        submission_from_response(&response.json().await?)
+            .ok_or_else(|| Error::Reputation("unexpected urlscan.io response".into()))
+    }
+}
+
+/// Read the scan uuid & report url out of a submission response
+pub(crate) fn submission_from_response(response: &Value) -> Option<ScanSubmission> {
+    Some(ScanSubmission {
+        uuid: response["uuid"].as_str()?.into(),
+        report_url: response["result"].as_str()?.into(),
+    })
+}
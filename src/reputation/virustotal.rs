@@ -0,0 +1,83 @@
+// VirusTotal (API v3) URL lookups
+use std::time::Duration;
+
+use base64::{engine::general_purpose, Engine as _};
+use futures::future::BoxFuture;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use super::{Detections, ReputationProvider, Verdict};
+use crate::{resolvers::verified_client, Error, Result};
+
+static ENDPOINT: &str = "https://www.virustotal.com/api/v3/urls/";
+
+/// Looks up the last VirusTotal analysis of a URL
+pub struct VirusTotal {
+    api_key: String,
+    timeout: Option<Duration>,
+    /// Shared by the lookups, `Err` when it could not be built
+    pub(crate) client: reqwest::Result<Client>,
+}
+
+impl VirusTotal {
+    /// Create a lookup client using the given VirusTotal API key
+    pub fn new<S: Into<String>>(api_key: S) -> Self {
+        Self {
+            api_key: api_key.into(),
+            timeout: None,
+            client: verified_client(),
+        }
+    }
+
+    /// Timeout of the lookup request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Detection counts of the last analysis, `None` if VirusTotal
+    /// has never analysed the url
+    pub async fn lookup(&self, url: &str) -> Result<Option<Detections>> {
+        let id = general_purpose::URL_SAFE_NO_PAD.encode(url);
+        let client = self
+            .client
+            .as_ref()
+            .map_err(|e| Error::Reqwest(e.to_string()))?;
+        let mut request = client
+            .get(format!("{}{}", ENDPOINT, id))
+            .header("x-apikey", &self.api_key);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(detections_from_response(&response.json().await?)),
+            status => Err(Error::Reputation(format!("virustotal returned {}", status))),
+        }
+    }
+}
+
+impl ReputationProvider for VirusTotal {
+    fn check<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Verdict>> {
+        Box::pin(async move {
+            Ok(self
+                .lookup(url)
+                .await?
+                .map(|detections| detections.verdict())
+                .unwrap_or(Verdict::Clean))
+        })
+    }
+}
+
+/// Read `last_analysis_stats` out of a url object
+pub(crate) fn detections_from_response(response: &Value) -> Option<Detections> {
+    let stats = response["data"]["attributes"]["last_analysis_stats"].as_object()?;
+    let count = |key: &str| stats.get(key).and_then(Value::as_u64).unwrap_or(0) as u32;
+    Some(Detections {
+        malicious: count("malicious"),
+        suspicious: count("suspicious"),
+        harmless: count("harmless"),
+        undetected: count("undetected"),
+    })
+}
@@ -1,23 +1,71 @@
 // adf.ly and its associated domains
-use super::from_url_not_200;
+use super::from_re;
+#[cfg(feature = "network")]
+use super::Session;
 use base64::{engine::general_purpose, Engine as _};
+#[cfg(feature = "network")]
 use futures::future::{ready, TryFutureExt};
 use percent_encoding::percent_decode_str;
-use std::{collections::VecDeque, str::from_utf8, time::Duration};
+use std::{collections::VecDeque, str::from_utf8};
+use url::Url;
 
+#[cfg(feature = "network")]
 use crate::{Error, Result};
 
-/// Decode the YSMM variable value to fetch the dest url
-fn decode_ysmm(ysmm: &str) -> Option<String> {
+/// Where the pages of adf.ly put the `ysmm` value: a script variable
+/// (`var ysmm = '..'`, `let ysmm="..."`, `window.ysmm = '..'`), a data
+/// attribute of the page, or a key of an embedded JSON config
+static YSMM_PATTERNS: [&str; 3] = [
+    r#"\bysmm\s*=\s*['"]([^'"]+)['"]"#,
+    r#"data-ysmm\s*=\s*['"]([^'"]+)['"]"#,
+    r#"['"]ysmm['"]\s*:\s*['"]([^'"]+)['"]"#,
+];
+
+/// The destination of an adf.ly page, from the first `ysmm` value that
+/// decodes
+pub(crate) fn from_page(html: &str) -> Option<String> {
+    YSMM_PATTERNS
+        .iter()
+        .filter_map(|pattern| from_re(html, pattern))
+        .find_map(|ysmm| decode_ysmm(&ysmm))
+}
+
+/// Decode the YSMM variable value to fetch the dest url: its characters
+/// are interleaved, then pairs of digits xor-ed (older pages skip that
+/// step), around a base64 payload
+pub(crate) fn decode_ysmm(ysmm: &str) -> Option<String> {
+    let data = deinterleave(ysmm)?;
+    [true, false].into_iter().find_map(|xored| {
+        let mut data = data.clone();
+        if xored {
+            unxor_digits(&mut data);
+        }
+        let buf = general_purpose::STANDARD
+            .decode(data.into_iter().collect::<String>())
+            .ok()?;
+        destination(from_utf8(&buf).ok()?)
+    })
+}
+
+/// Put back the characters of a `ysmm` value in order: the even ones come
+/// first, then the odd ones reversed
+fn deinterleave(ysmm: &str) -> Option<VecDeque<char>> {
     let mut data = VecDeque::<char>::new();
 
     for c in ysmm.chars().collect::<Vec<_>>().chunks(2) {
-        data.push_back(c[0]);
-        data.push_front(c[1]);
+        let [even, odd] = c else {
+            return None;
+        };
+        data.push_back(*even);
+        data.push_front(*odd);
     }
 
     data.rotate_left(data.len() / 2);
+    Some(data)
+}
 
+/// Undo the xor of the pairs of digits of a `ysmm` value
+fn unxor_digits(data: &mut VecDeque<char>) {
     data.iter()
         .enumerate()
         .filter_map(|(j, val)| {
@@ -32,35 +80,67 @@ fn decode_ysmm(ysmm: &str) -> Option<String> {
         .for_each(|items| {
             if let [x, y] = items {
                 let xor = x.1 ^ y.1;
-                if xor < 10 {
-                    data[x.0] = xor.to_string().parse().unwrap();
+                if let Some(digit) = char::from_digit(xor as u32, 10) {
+                    data[x.0] = digit;
                 }
             }
         });
+}
 
-    let buf = general_purpose::STANDARD
-        .decode(data.drain(..).collect::<String>())
-        .unwrap();
+/// The destination in a decoded `ysmm` payload: a `dest=` parameter
+/// between 16 characters of padding, or the url itself, padded or not
+fn destination(payload: &str) -> Option<String> {
+    let padded = payload.get(16..payload.len().checked_sub(16)?);
+    if let Some(dest) = padded.and_then(|inner| inner.split("dest=").nth(1)) {
+        return Some(percent_decode_str(dest).decode_utf8_lossy().into());
+    }
+    [padded, Some(payload)]
+        .into_iter()
+        .flatten()
+        .map(|url| percent_decode_str(url).decode_utf8_lossy().into_owned())
+        .find(|url| Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")))
+}
 
-    from_utf8(&buf).ok().and_then(|v| {
-        v[16..v.len() - 16]
-            .split("dest=")
-            .nth(1)
-            .map(|url| percent_decode_str(url).decode_utf8_lossy().into())
-    })
+/// The `ysmm` value of an adf.ly page for `destination`: the reverse of
+/// `decode_ysmm`, to serve test pages
+#[cfg(all(any(test, feature = "testing"), feature = "network"))]
+pub(crate) fn encode_ysmm(destination: &str) -> String {
+    let padding = "0123456789abcdef";
+    let plain = format!(
+        "{0}dest={1}{0}",
+        padding,
+        percent_encoding::utf8_percent_encode(destination, percent_encoding::NON_ALPHANUMERIC)
+    );
+    encode_payload(&plain, true)
+}
+
+/// The `ysmm` value of a decoded payload, with its digits xor-ed or not
+#[cfg(all(any(test, feature = "testing"), feature = "network"))]
+pub(crate) fn encode_payload(payload: &str, xored: bool) -> String {
+    let mut data: Vec<char> = general_purpose::STANDARD.encode(payload).chars().collect();
+    // pairs of digits are xor-ed when decoding, when that gives a digit
+    let digits: Vec<usize> = (0..data.len())
+        .filter(|&i| xored && data[i].is_ascii_digit())
+        .collect();
+    for pair in digits.chunks_exact(2) {
+        let x = data[pair[0]].to_digit(10).unwrap_or_default();
+        let y = data[pair[1]].to_digit(10).unwrap_or_default();
+        if x ^ y < 10 {
+            data[pair[0]] = char::from_digit(x ^ y, 10).unwrap_or('0');
+        }
+    }
+    // even characters come first when decoding, then the odd ones reversed
+    let half = data.len() / 2;
+    (0..half)
+        .flat_map(|i| [data[i], data[data.len() - 1 - i]])
+        .collect()
 }
 
 /// URL Expander for ADF.LY and its associated shortners
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    from_url_not_200(url, timeout)
-        .and_then(|html| {
-            ready(
-                html.split("ysmm = '")
-                    .nth(1)
-                    .and_then(|r| r.split("';").next())
-                    .and_then(decode_ysmm)
-                    .ok_or(Error::NoString),
-            )
-        })
+#[cfg(feature = "network")]
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    session
+        .page_not_200(url)
+        .and_then(|html| ready(from_page(&html).ok_or(Error::ParseFailure { resolver: "adfly" })))
         .await
 }
@@ -1,23 +1,73 @@
 // adfoc.us shortening service
-use std::time::Duration;
+use super::{decode_entities, from_re, Redirects, Session};
+use regex::Regex;
+use reqwest::Method;
+use url::Url;
 
-use super::from_url_not_200;
+use crate::{Error, Result};
 
-use futures::future::{ready, TryFutureExt};
+/// The click-through endpoint behind the skip button, redirecting to the
+/// destination
+static CLICK_THROUGH: &str = r#"['"]([^'"]*/serve/click/?\?[^'"]+)['"]"#;
 
-use crate::{Error, Result};
+/// The JSON config the page assigns to its `adfocus` variable
+static JSON_CONFIG: &str = r#"\badfocus\s*=\s*(\{[^}]*\})"#;
 
-/// URL Expander for ADFOC.US
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    from_url_not_200(url, timeout)
-        .and_then(|html| {
-            ready(
-                html.split("click_url = \"")
-                    .nth(1)
-                    .and_then(|r| r.split("\";").next())
-                    .map(Into::into)
-                    .ok_or(Error::NoString),
-            )
-        })
+/// A destination in the JSON config
+static CONFIG_URL: &str = r#"['"](?:click_url|destination|url)['"]\s*:\s*['"]([^'"]+)['"]"#;
+
+/// The destination in the legacy `click_url` script variable
+static LEGACY_VARIABLE: &str = r#"\bclick_url\s*=\s*['"]([^'"]+)['"]"#;
+
+/// URL Expander for ADFOC.US: the click-through endpoint of the page, then
+/// its embedded JSON config, then the legacy `click_url` variable, so that
+/// a template change does not take them all out
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    let html = session.page_not_200(url).await?;
+    if let Some(destination) = click_through(url, &html, session).await {
+        return Ok(destination);
+    }
+    from_page(url, &html).ok_or(Error::ParseFailure {
+        resolver: "adfocus",
+    })
+}
+
+/// Where the click-through endpoint of the page redirects to, when it
+/// leaves adfoc.us
+async fn click_through(url: &str, html: &str, session: &Session<'_>) -> Option<String> {
+    let page = Url::parse(url).ok()?;
+    let click = page.join(&from_re(html, CLICK_THROUGH)?).ok()?;
+    let reply = session
+        .send(Method::GET, click.as_str(), Redirects::SameHost)
         .await
+        .ok()?;
+    (reply.url.host_str() != page.host_str()).then(|| reply.url.into())
+}
+
+/// The destination written in the page: the first url of its JSON config,
+/// then of its legacy script variable, that leaves adfoc.us
+fn from_page(url: &str, html: &str) -> Option<String> {
+    let page = Url::parse(url).ok()?;
+    let config = from_re(html, JSON_CONFIG).unwrap_or_default();
+    let candidates = captures(&config, CONFIG_URL)
+        .map(|url| url.replace("\\/", "/").replace("\\u0026", "&"))
+        .chain(captures(html, LEGACY_VARIABLE));
+    candidates
+        .filter_map(|candidate| Url::parse(&candidate).ok())
+        .find(|candidate| candidate.host_str() != page.host_str())
+        .map(String::from)
+}
+
+/// The first group of every match of a pattern
+fn captures(txt: &str, p: &str) -> impl Iterator<Item = String> {
+    let urls: Vec<String> = Regex::new(p)
+        .map(|pattern| {
+            pattern
+                .captures_iter(txt)
+                .filter_map(|c| c.get(1))
+                .map(|x| decode_entities(x.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    urls.into_iter()
 }
@@ -0,0 +1,48 @@
+// Charset-aware decoding of HTML pages
+use encoding_rs::{Encoding, UTF_8};
+use regex::bytes::Regex;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::{transport::HttpResponse, Result};
+
+/// How far into a page `<meta charset>` declarations are looked for
+static META_SNIFF_LEN: usize = 1024;
+
+/// Body of the response decoded with the charset of its `Content-Type`,
+/// else of its `<meta>` declaration, else as UTF-8
+pub(crate) async fn text(response: HttpResponse) -> Result<String> {
+    let content_type = response
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map(String::from);
+    let body = response.bytes().await?;
+    Ok(decode_html(&body, content_type.as_deref()))
+}
+
+/// Decode an HTML page; a byte order mark takes precedence over any declaration
+pub(crate) fn decode_html(body: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(charset_of_content_type)
+        .or_else(|| charset_of_meta(&body[..body.len().min(META_SNIFF_LEN)]))
+        .unwrap_or(UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+/// `charset` parameter of a Content-Type value
+fn charset_of_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))
+}
+
+/// `<meta charset="..">` or `<meta http-equiv="Content-Type" content="..; charset=..">`
+fn charset_of_meta(head: &[u8]) -> Option<&'static Encoding> {
+    Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?([a-z0-9_:.\-]+)"#)
+        .ok()?
+        .captures(head)
+        .and_then(|c| Encoding::for_label(c.get(1)?.as_bytes()))
+}
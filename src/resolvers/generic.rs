@@ -1,18 +1,18 @@
 // Generic Resolver
-use std::time::Duration;
-
-use super::{custom_redirect_policy, get_client_builder};
-
-use futures::future::{ready, TryFutureExt};
+use super::{Redirects, Session};
+use reqwest::Method;
+use url::Url;
 
 use crate::Result;
 
 /// Generic URL Expander
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    let custom = custom_redirect_policy();
-    ready(get_client_builder(timeout).redirect(custom).build())
-        .and_then(|client| async move { client.get(url).send().await })
-        .map_ok(|response| response.url().as_str().into())
-        .err_into()
-        .await
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    let reply = session.send(Method::GET, url, Redirects::SameHost).await?;
+    let expanded = reply.url.to_string();
+    if reply.url.host_str() == Url::parse(url).ok().as_ref().and_then(Url::host_str) {
+        // a shortener answering with a page of its own may not know the
+        // link, or be a parked domain: the page tells
+        session.text(reply.response).await.ok();
+    }
+    Ok(expanded)
 }
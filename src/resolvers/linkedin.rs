@@ -4,21 +4,20 @@
 // 2. Interstitial warning page with URL in HTML (when flagged/rate-limited)
 // We try both approaches for robustness
 
-use crate::resolvers::{from_url, generic};
+use crate::resolvers::{decode_entities, generic, Session};
 use futures::future::{ready, TryFutureExt};
-use std::time::Duration;
 
 use crate::{Error, Result};
 
 /// LinkedIn URL Expander
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
     // First try standard HTTP redirect (most common LinkedIn behavior)
-    let expanded_url = generic::unshort(url, timeout).await?;
+    let expanded_url = generic::unshort(url, session).await?;
 
     // If we're still on LinkedIn domain, try parsing the interstitial page
     Ok(
         if expanded_url.contains("linkedin.com") || expanded_url.contains("lnkd.in") {
-            match get_from_html(url, timeout).await {
+            match get_from_html(url, session).await {
                 Ok(u) => u,
                 Err(_) => expanded_url, // Fallback to whatever generic gave us
             }
@@ -28,8 +27,9 @@ pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<Stri
     )
 }
 
-async fn get_from_html(url: &str, timeout: Option<Duration>) -> Result<String> {
-    from_url(url, timeout)
+async fn get_from_html(url: &str, session: &Session<'_>) -> Result<String> {
+    session
+        .page(url)
         .and_then(|html| {
             ready(
                 // Parse the interstitial warning page
@@ -37,7 +37,7 @@ async fn get_from_html(url: &str, timeout: Option<Duration>) -> Result<String> {
                     .nth(1)
                     .and_then(|r| r.split("href=\"").nth(1))
                     .and_then(|r| r.split("\">").next())
-                    .map(|r| r.to_string())
+                    .map(decode_entities)
                     .ok_or(Error::NoString),
             )
         })
@@ -1,24 +1,143 @@
+#[cfg(feature = "network")]
 use core::time::Duration;
 use regex::Regex;
-use reqwest::{redirect::Policy, Client, ClientBuilder, StatusCode};
+#[cfg(feature = "network")]
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CACHE_CONTROL, COOKIE,
+        ETAG, LAST_MODIFIED, LOCATION, RETRY_AFTER,
+    },
+    Client, ClientBuilder, Method, StatusCode,
+};
+#[cfg(feature = "network")]
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Instant, SystemTime},
+};
+#[cfg(feature = "network")]
+use url::Url;
 
 pub(crate) mod adfly;
+#[cfg(feature = "network")]
 pub(crate) mod adfocus;
+#[cfg(feature = "network")]
+pub(crate) mod charset;
+#[cfg(feature = "network")]
 pub(crate) mod generic;
+#[cfg(feature = "network")]
 pub(crate) mod http_redirect;
+#[cfg(feature = "network")]
 pub(crate) mod linkedin;
+#[cfg(feature = "network")]
+pub(crate) mod not_found;
 pub(crate) mod redirect;
 pub(crate) mod refresh;
+#[cfg(feature = "network")]
+pub(crate) mod rluru;
+#[cfg(feature = "network")]
 pub(crate) mod shorturl;
+#[cfg(feature = "network")]
 pub(crate) mod surlli;
 
-use futures::future::{ready, TryFutureExt};
-
-use crate::Result;
+#[cfg(feature = "network")]
+use crate::{
+    dns,
+    expander::{HopInfo, HopTiming, RedirectAction, Settings},
+    health,
+    transport::{HttpRequest, HttpResponse, HttpTransport},
+    Error, Result,
+};
 
+#[cfg(feature = "network")]
 static UA: &str = "curl/7.72.0";
 
+/// Redirects followed before giving up, unless configured otherwise
+#[cfg(feature = "network")]
+pub(crate) static MAX_REDIRECTS: usize = 10;
+
+/// How the urls of a service are resolved
+#[cfg(feature = "network")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Resolver {
+    /// `ysmm` variable of an interstitial page (adf.ly)
+    Adfly,
+    /// JavaScript-based redirects
+    Redirect,
+    /// HTTP 3xx redirects, followed to the end
+    HttpRedirect,
+    /// Meta refresh
+    Refresh,
+    Adfocus,
+    Linkedin,
+    Rluru,
+    ShortUrl,
+    Surlli,
+    /// HTTP 3xx redirects, followed until another host
+    Generic,
+}
+
+#[cfg(feature = "network")]
+impl Resolver {
+    /// The resolver of a service
+    pub(crate) fn of(service: &str) -> Self {
+        match service {
+            "adf.ly" | "atominik.com" | "fumacrom.com" | "intamema.com" | "j.gs" | "q.gs" => {
+                Self::Adfly
+            }
+            "gns.io" | "ity.im" | "ldn.im" | "nowlinks.net" | "tinyurl.com" | "tr.im"
+            | "vzturl.com" => Self::Redirect,
+            "u.to" => Self::HttpRedirect,
+            "cutt.us" | "soo.gd" => Self::Refresh,
+            "adfoc.us" => Self::Adfocus,
+            "lnkd.in" => Self::Linkedin,
+            "rlu.ru" => Self::Rluru,
+            "shorturl.at" => Self::ShortUrl,
+            "surl.li" => Self::Surlli,
+            _ => Self::Generic,
+        }
+    }
+}
+
+/// Dispatch a validated url to the resolver of its service
+#[cfg(feature = "network")]
+pub(crate) async fn unshort(service: &str, url: &str, session: &Session<'_>) -> Result<String> {
+    match Resolver::of(service) {
+        Resolver::Adfly => adfly::unshort(url, session).await,
+        Resolver::Redirect => redirect::unshort(url, session).await,
+        Resolver::HttpRedirect => http_redirect::unshort(url, session).await,
+        Resolver::Refresh => refresh::unshort(url, session).await,
+        Resolver::Adfocus => adfocus::unshort(url, session).await,
+        Resolver::Linkedin => linkedin::unshort(url, session).await,
+        Resolver::Rluru => rluru::unshort(url, session).await,
+        Resolver::ShortUrl => shorturl::unshort(url, session).await,
+        Resolver::Surlli => surlli::unshort(url, session).await,
+        Resolver::Generic => generic::unshort(url, session).await,
+    }
+}
+
+/// Resolve a url that was given without a scheme: over https first, then
+/// over http when the https connection (or its TLS handshake) fails.
+/// Returns the expanded url and the scheme that worked.
+#[cfg(feature = "network")]
+pub(crate) async fn unshort_scheme_less(
+    service: &str,
+    url: &str,
+    session: &Session<'_>,
+    http_fallback: bool,
+) -> Result<(String, String)> {
+    match unshort(service, url, session).await {
+        Err(Error::Connect(_)) if http_fallback => {
+            let mut http = Url::parse(url).map_err(|_| Error::NoString)?;
+            http.set_scheme("http").map_err(|_| Error::NoString)?;
+            let expanded = unshort(service, http.as_str(), session).await?;
+            Ok((expanded, "http".into()))
+        }
+        result => result.map(|expanded| (expanded, "https".into())),
+    }
+}
+
 /// get the reqwest ClientBuilder
+#[cfg(feature = "network")]
 pub(crate) fn get_client_builder(timeout: Option<Duration>) -> ClientBuilder {
     match timeout {
         Some(x) => Client::builder().timeout(x),
@@ -28,65 +147,393 @@ pub(crate) fn get_client_builder(timeout: Option<Duration>) -> ClientBuilder {
     .danger_accept_invalid_certs(true)
 }
 
-/// Reqwest Custom Redirect Policy
-pub(crate) fn custom_redirect_policy() -> Policy {
-    Policy::custom(|attempt| {
-        let n_attempt = attempt.previous().len();
-        if attempt.previous()[0].host() != attempt.previous()[n_attempt - 1].host() {
-            attempt.stop()
+/// A client checking certificates, unlike the one following short urls:
+/// for the APIs its requests give a key to, and the answers they trust
+#[cfg(any(
+    feature = "doh",
+    feature = "safe-browsing",
+    feature = "urlscan",
+    feature = "virustotal"
+))]
+pub(crate) fn verified_client() -> reqwest::Result<Client> {
+    Client::builder().user_agent(UA).build()
+}
+
+/// Which redirects a request follows
+#[cfg(feature = "network")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Redirects {
+    /// Follow redirects until a url on another host has been fetched
+    SameHost,
+    /// Follow every redirect
+    All,
+}
+
+/// Last response of a request, and the url its redirects ended at
+#[cfg(feature = "network")]
+pub(crate) struct Reply {
+    /// Either the url of the response, or the target of a redirect
+    /// the redirect policy decided not to follow
+    pub(crate) url: Url,
+    pub(crate) response: HttpResponse,
+}
+
+/// State of a single expansion, shared by the resolvers
+#[cfg(feature = "network")]
+pub(crate) struct Session<'a> {
+    transport: &'a dyn HttpTransport,
+    settings: &'a Settings,
+    hops: Mutex<Vec<HopInfo>>,
+    /// Status and `Retry-After` delay of the last response of the shortener
+    shortener_reply: Mutex<Option<(u16, Option<Duration>)>>,
+    /// The last page of the shortener that was read
+    shortener_page: Mutex<Option<String>>,
+    /// Cookies (`name=value`) sent to the host of the short url
+    cookies: Mutex<Vec<String>>,
+    started: Instant,
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "network")]
+impl<'a> Session<'a> {
+    /// `transport` must not follow redirects, the session does it to record every hop
+    pub(crate) fn new(transport: &'a dyn HttpTransport, settings: &'a Settings) -> Self {
+        Self {
+            transport,
+            settings,
+            hops: Mutex::new(vec![]),
+            shortener_reply: Mutex::new(None),
+            shortener_page: Mutex::new(None),
+            cookies: Mutex::new(vec![]),
+            started: Instant::now(),
+            timeout: None,
+        }
+    }
+
+    /// Override the timeout of the client for every request of the session
+    pub(crate) fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Every request made during the session
+    pub(crate) fn into_hops(self) -> Vec<HopInfo> {
+        self.hops
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Status and `Retry-After` delay of the last response of the shortener
+    pub(crate) fn shortener_reply(&self) -> Option<(u16, Option<Duration>)> {
+        *self
+            .shortener_reply
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Status of the response to the short url itself
+    pub(crate) fn first_status(&self) -> Option<u16> {
+        let hops = self.hops.lock().unwrap_or_else(PoisonError::into_inner);
+        hops.first().map(|hop| hop.status)
+    }
+
+    /// Whether the first host the shortener led to is a parking service:
+    /// either the shortener domain is parked, or the link is to a listing
+    pub(crate) fn leads_to_parking(&self) -> bool {
+        let hops = self.hops.lock().unwrap_or_else(PoisonError::into_inner);
+        let shortener = hops.first().and_then(|hop| host(&hop.url));
+        hops.iter()
+            .find(|hop| host(&hop.url) != shortener)
+            .is_some_and(|hop| health::is_parking_url(&hop.url))
+    }
+
+    /// The url of the last request
+    pub(crate) fn last_url(&self) -> Option<String> {
+        let hops = self.hops.lock().unwrap_or_else(PoisonError::into_inner);
+        hops.last().map(|hop| hop.url.clone())
+    }
+
+    /// The last page of the shortener that was read
+    pub(crate) fn shortener_page(&self) -> Option<String> {
+        self.shortener_page
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Send a `name=value` cookie with the next requests to the host of the
+    /// short url, as a browser would once a page set it
+    pub(crate) fn set_cookie(&self, cookie: String) {
+        self.cookies
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(cookie);
+    }
+
+    /// Send a request to the url, following redirects as asked
+    pub(crate) async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        redirects: Redirects,
+    ) -> Result<Reply> {
+        self.execute(method, url, redirects, false).await
+    }
+
+    /// Get Page Content if status!=200
+    pub(crate) async fn page_not_200(&self, url: &str) -> Result<String> {
+        let response = self
+            .execute(Method::GET, url, Redirects::All, true)
+            .await?
+            .response;
+        if response.status == StatusCode::OK {
+            Err(Error::NoString)
         } else {
-            attempt.follow()
+            self.text(response).await
         }
-    })
-}
-
-/// Get Page Content if status!=200
-pub(crate) async fn from_url_not_200(url: &str, timeout: Option<Duration>) -> Result<String> {
-    ready(get_client_builder(timeout).build())
-        .and_then(|client| async move {
-            client
-                .get(url)
-                .header(
-                    "Accept",
-                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
-                )
-                .header("Accept-Language", "en-US,en;q=0.5")
-                .header("Cache-Control", "no-cache")
-                .send()
-                .await
-        })
-        .err_into()
-        .and_then(|response| async move {
-            if response.status() == StatusCode::OK {
-                Err(crate::error::Error::NoString)
-            } else {
-                Ok(response.text().await?)
+    }
+
+    /// get page content irrespective of status code
+    pub(crate) async fn page(&self, url: &str) -> Result<String> {
+        let response = self
+            .execute(Method::GET, url, Redirects::All, true)
+            .await?
+            .response;
+        self.text(response).await
+    }
+
+    /// Body of a response, decoded with its charset
+    pub(crate) async fn text(&self, response: HttpResponse) -> Result<String> {
+        let started = Instant::now();
+        let text = charset::text(response).await.map_err(|e| self.elapsed(e))?;
+        let mut hops = self.hops.lock().unwrap_or_else(PoisonError::into_inner);
+        let shortener = hops.first().and_then(|hop| host(&hop.url));
+        if let Some(hop) = hops.last_mut() {
+            hop.timing.total += started.elapsed();
+            if host(&hop.url) == shortener {
+                *self
+                    .shortener_page
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some(text.clone());
             }
+        }
+        Ok(text)
+    }
+
+    /// Fill in the time spent on the expansion when a transport timed out
+    fn elapsed(&self, e: Error) -> Error {
+        match e {
+            Error::Timeout { stage, .. } => Error::Timeout {
+                elapsed: self.started.elapsed(),
+                stage,
+            },
+            e => e,
+        }
+    }
+
+    async fn execute(
+        &self,
+        method: Method,
+        url: &str,
+        redirects: Redirects,
+        browser: bool,
+    ) -> Result<Reply> {
+        let first = Url::parse(url).map_err(|_| Error::NoString)?;
+        let mut current = first.clone();
+        for _ in 0..=self.settings.max_redirects {
+            let mut headers = HeaderMap::new();
+            if browser {
+                headers.insert(
+                    ACCEPT,
+                    HeaderValue::from_static(
+                        "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                    ),
+                );
+                headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.5"));
+                headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+            } else if let Some(accept_encoding) = &self.settings.accept_encoding {
+                if let Ok(value) = HeaderValue::from_str(accept_encoding) {
+                    headers.insert(ACCEPT_ENCODING, value);
+                }
+            }
+            let cookies = self
+                .cookies
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .join("; ");
+            if !cookies.is_empty() && current.host() == first.host() {
+                if let Ok(value) = HeaderValue::from_str(&cookies) {
+                    headers.insert(COOKIE, value);
+                }
+            }
+            let request = HttpRequest {
+                method: method.clone(),
+                url: current.clone(),
+                headers,
+                timeout: self.timeout,
+            };
+            let started = Instant::now();
+            let (response, dns) = dns::timed_lookup(self.transport.send(request)).await;
+            let response = response.map_err(|e| self.elapsed(e))?;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                url = %current,
+                status = response.status.as_u16(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "hop"
+            );
+            let ttfb = started.elapsed();
+            let timing = HopTiming {
+                dns,
+                ttfb,
+                total: ttfb,
+            };
+            let location = redirect_target(&current, &response);
+            self.record(
+                &current,
+                response.status,
+                &response.headers,
+                location,
+                timing,
+            )
+            .await;
+            if current.host() == first.host() {
+                let reply = (response.status.as_u16(), retry_after(&response));
+                *self
+                    .shortener_reply
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some(reply);
+            }
+
+            let next = match redirects {
+                Redirects::SameHost if current.host() != first.host() => None,
+                _ => redirect_target(&current, &response),
+            };
+            let action = match (&next, &self.settings.redirect_policy) {
+                (Some(next), Some(policy)) => policy(next, self.hop_count() - 1),
+                _ => RedirectAction::Continue,
+            };
+            if let Some(next) = &next {
+                self.settings.check_scheme(next)?;
+                if self.settings.refuse_downgrades && is_downgrade(&current, next) {
+                    return Err(Error::Downgrade(next.as_str().into()));
+                }
+            }
+            current = match (next, action) {
+                (Some(next), RedirectAction::Continue) => next,
+                (Some(_), RedirectAction::Rewrite(rewritten)) => {
+                    self.settings.check_scheme(&rewritten)?;
+                    rewritten
+                }
+                (Some(next), RedirectAction::Stop) => {
+                    return Ok(Reply {
+                        url: next,
+                        response,
+                    })
+                }
+                (None, _) => {
+                    return Ok(Reply {
+                        url: current,
+                        response,
+                    })
+                }
+            };
+        }
+        Err(Error::TooManyRedirects)
+    }
+
+    /// Number of requests made so far
+    fn hop_count(&self) -> usize {
+        self.hops
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Remember the hop and notify the callback
+    async fn record(
+        &self,
+        url: &Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+        location: Option<Url>,
+        timing: HopTiming,
+    ) {
+        let header = |name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            Some(String::from(value))
+        };
+        let hop = {
+            let mut hops = self.hops.lock().unwrap_or_else(PoisonError::into_inner);
+            let downgrade = hops
+                .last()
+                .and_then(|previous| Url::parse(&previous.url).ok())
+                .is_some_and(|previous| is_downgrade(&previous, url));
+            let hop = HopInfo {
+                index: hops.len(),
+                url: url.as_str().into(),
+                status: status.as_u16(),
+                timing,
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+                downgrade,
+                location: location.map(String::from),
+            };
+            hops.push(hop.clone());
+            hop
+        };
+        if let Some(on_hop) = &self.settings.on_hop {
+            on_hop(hop).await;
+        }
+    }
+}
+
+/// Whether going from a url to the next drops https for plain http
+#[cfg(feature = "network")]
+fn is_downgrade(from: &Url, to: &Url) -> bool {
+    from.scheme() == "https" && to.scheme() == "http"
+}
+
+/// The host of a url
+#[cfg(feature = "network")]
+pub(crate) fn host(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(String::from)
+}
+
+/// The `Retry-After` delay of a response, given in seconds or as a date
+#[cfg(feature = "network")]
+fn retry_after(response: &HttpResponse) -> Option<Duration> {
+    response
+        .headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .and_then(|value| match value.parse() {
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => httpdate::parse_http_date(value)
+                .ok()
+                .map(|date| date.duration_since(SystemTime::now()).unwrap_or_default()),
         })
-        .await
-}
-
-/// get page content irrespective of status code
-pub(crate) async fn from_url(url: &str, timeout: Option<Duration>) -> Result<String> {
-    ready(get_client_builder(timeout).build())
-        .and_then(|client| async move {
-            client
-                .get(url)
-                .header(
-                    "Accept",
-                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
-                )
-                .header("Accept-Language", "en-US,en;q=0.5")
-                .header("Cache-Control", "no-cache")
-                .send()
-                .await
-        })
-        .err_into()
-        .and_then(|response| async move { Ok(response.text().await?) })
-        .await
 }
 
-/// Extract text from regex pattern
+/// Where a redirect response points to
+#[cfg(feature = "network")]
+fn redirect_target(url: &Url, response: &HttpResponse) -> Option<Url> {
+    match response.status {
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::SEE_OTHER
+        | StatusCode::TEMPORARY_REDIRECT
+        | StatusCode::PERMANENT_REDIRECT => response
+            .headers
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .and_then(|location| url.join(location).ok()),
+        _ => None,
+    }
+}
+
+/// Extract text from regex pattern, decoding its HTML entities
 fn from_re(txt: &str, p: &str) -> Option<String> {
     Regex::new(p)
         .ok()
@@ -95,5 +542,56 @@ fn from_re(txt: &str, p: &str) -> Option<String> {
                 .captures(txt)
                 .and_then(|c| c.iter().skip(1).flatten().next())
         })
-        .map(|x| x.as_str().into())
+        .map(|x| decode_entities(x.as_str()))
+}
+
+/// Decode the HTML character references of a value taken from a page
+/// (`&amp;`, `&#38;`, `&#x26;`, ...); unknown references are kept as is
+pub(crate) fn decode_entities(txt: &str) -> String {
+    let mut decoded = String::with_capacity(txt.len());
+    let mut rest = txt;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        match reference.and_then(decode_reference) {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[reference.map(str::len).unwrap_or_default() + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Character of a reference, without its `&` and `;`
+fn decode_reference(reference: &str) -> Option<char> {
+    match reference {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "sol" => Some('/'),
+        "equals" => Some('='),
+        "quest" => Some('?'),
+        "num" => Some('#'),
+        "percnt" => Some('%'),
+        _ => {
+            let code = reference.strip_prefix('#')?;
+            match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => code.parse().ok(),
+            }
+            .and_then(char::from_u32)
+        }
+    }
 }
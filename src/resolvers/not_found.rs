@@ -0,0 +1,63 @@
+// Shorteners answering the codes they do not know with a page of their own
+use url::Url;
+
+/// How a shortener tells it does not know a code
+enum Sign {
+    /// It redirects to this error page
+    Url(&'static str),
+    /// It answers with a page saying so (lowercased)
+    Page(&'static str),
+}
+
+/// What the shorteners answer for the links they do not know, or no longer
+/// have, instead of a 404
+static NOT_FOUND: [(&str, Sign); 12] = [
+    ("bit.ly", Sign::Page("this bitlink doesn't exist")),
+    ("cutt.ly", Sign::Page("link does not exist")),
+    ("goo.gl", Sign::Page("dynamic link not found")),
+    ("is.gd", Sign::Page("short url not found")),
+    ("kutt.it", Sign::Url("https://kutt.it/404")),
+    ("ow.ly", Sign::Url("https://ow.ly/404")),
+    ("rebrand.ly", Sign::Page("link not found")),
+    ("rb.gy", Sign::Page("this link doesn't exist")),
+    ("t.ly", Sign::Page("link not found")),
+    ("tiny.cc", Sign::Page("this short link does not exist")),
+    (
+        "tinyurl.com",
+        Sign::Url("https://tinyurl.com/app/nolongeravailable"),
+    ),
+    ("v.gd", Sign::Page("short url not found")),
+];
+
+/// The host of a url, without `www.`
+fn bare_host(url: &Url) -> Option<&str> {
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host))
+}
+
+/// Whether the shortener did not know the short url: it sent it to its
+/// home page or to its error page, or answered with a "no such link" page
+pub(crate) fn is_unknown_link(
+    service: &str,
+    short_url: &str,
+    expanded: Option<&str>,
+    page: Option<&str>,
+) -> bool {
+    let expanded = expanded.and_then(|expanded| Url::parse(expanded).ok());
+    if let (Ok(short), Some(expanded)) = (Url::parse(short_url), &expanded) {
+        let home = matches!(expanded.path(), "" | "/") && expanded.query().is_none();
+        if home && short.path() != "/" && bare_host(expanded) == bare_host(&short) {
+            return true;
+        }
+    }
+    let page = page.map(str::to_lowercase);
+    NOT_FOUND
+        .iter()
+        .filter(|(known, _)| *known == service)
+        .any(|(_, sign)| match sign {
+            Sign::Url(error_page) => expanded
+                .as_ref()
+                .is_some_and(|expanded| expanded.as_str().starts_with(error_page)),
+            Sign::Page(phrase) => page.as_ref().is_some_and(|page| page.contains(phrase)),
+        })
+}
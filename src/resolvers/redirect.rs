@@ -1,9 +1,14 @@
 // Shortner services that Redirects
-use super::{from_re, get_client_builder};
-use std::time::Duration;
+use super::from_re;
+#[cfg(feature = "network")]
+use super::{Redirects, Session};
+#[cfg(feature = "network")]
+use reqwest::Method;
 
+#[cfg(feature = "network")]
 use futures::future::{ready, TryFutureExt};
 
+#[cfg(feature = "network")]
 use crate::{Error, Result};
 
 static RE_PATTERNS: [&str; 6] = [
@@ -16,11 +21,16 @@ static RE_PATTERNS: [&str; 6] = [
 ];
 
 /// Shortner services that employ different Redirect mechanisms
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    ready(get_client_builder(timeout).build())
-        .and_then(|client| async move { client.get(url).send().await })
-        .and_then(|response| async move { response.text().await })
-        .err_into()
-        .and_then(|text| ready(from_re(&text, &RE_PATTERNS.join("|")).ok_or(Error::NoString)))
+#[cfg(feature = "network")]
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    session
+        .send(Method::GET, url, Redirects::All)
+        .and_then(|reply| session.text(reply.response))
+        .and_then(|text| ready(from_page(&text).ok_or(Error::NoString)))
         .await
 }
+
+/// The target of a JavaScript redirect (or of one of the known interstitials)
+pub(crate) fn from_page(html: &str) -> Option<String> {
+    from_re(html, &RE_PATTERNS.join("|"))
+}
@@ -1,14 +1,42 @@
 // All sites that performs Meta Refresh
-use super::{from_re, from_url_not_200};
-use std::time::Duration;
-
+use super::from_re;
+#[cfg(feature = "network")]
+use super::Session;
+#[cfg(feature = "network")]
 use futures::future::{ready, TryFutureExt};
 
+#[cfg(feature = "network")]
 use crate::{Error, Result};
 
-/// URL Expander for Shorten links that uses Meta Refresh to redirect
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    from_url_not_200(url, timeout)
-        .and_then(|html| ready(from_re(&html, "URL=([^\"]*)").ok_or(Error::NoString)))
+/// The target of a JavaScript redirect of a page
+#[cfg(feature = "network")]
+static JS_REDIRECT: &str = r#"location(?:\.href)?\s*=\s*['"]([^'"]+)['"]|location\.(?:replace|assign)\(\s*['"]([^'"]+)['"]"#;
+
+/// The link of the `<noscript>` block of a page
+#[cfg(feature = "network")]
+static NOSCRIPT_ANCHOR: &str = r#"(?is)<noscript>.*?<a\s[^>]*?href\s*=\s*['"]([^'"]+)['"]"#;
+
+/// URL Expander for Shorten links that uses Meta Refresh to redirect, with
+/// the JavaScript redirect and the `<noscript>` link of their pages
+/// (cutt.us, soo.gd) as fallbacks, so that any one of them can change
+#[cfg(feature = "network")]
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    session
+        .page_not_200(url)
+        .and_then(|html| {
+            ready(
+                from_page(&html)
+                    .or_else(|| from_re(&html, JS_REDIRECT))
+                    .or_else(|| from_re(&html, NOSCRIPT_ANCHOR))
+                    .ok_or(Error::ParseFailure {
+                        resolver: "refresh",
+                    }),
+            )
+        })
         .await
 }
+
+/// The target of a meta refresh
+pub(crate) fn from_page(html: &str) -> Option<String> {
+    from_re(html, "URL=([^\"]*)")
+}
@@ -0,0 +1,53 @@
+// rlu.ru shortening service
+use super::{from_re, redirect, retry_after, Redirects, Session};
+use reqwest::{Method, StatusCode};
+
+use crate::{Error, Result};
+
+/// The continue link of the warning page shown for "suspicious" links
+static CONTINUE_LINK: &str = r#"(?i)<a\s[^>]*?id\s*=\s*['"](?:continue|proceed)['"][^>]*?href\s*=\s*['"]([^'"]+)['"]|<a\s[^>]*?href\s*=\s*['"]([^'"]+)['"][^>]*>\s*(?:continue|proceed|продолжить|перейти)"#;
+
+/// Phrases of the page answered when throttling, lowercased
+static THROTTLE_MARKERS: [&str; 2] = ["too many requests", "слишком много запросов"];
+
+/// URL Expander for rlu.ru: its interstitial, or the continue link of its
+/// warning page; throttling fails with `Error::ServiceBlocked`
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    let reply = session.send(Method::GET, url, Redirects::All).await?;
+    // redirected away: what answered is the destination, not rlu.ru
+    if reply.url.host_str() != Some("rlu.ru") {
+        return Ok(reply.url.into());
+    }
+    let status = reply.response.status;
+    let blocked = Error::ServiceBlocked {
+        service: "rlu.ru".into(),
+        status: status.as_u16(),
+        retry_after: retry_after(&reply.response),
+    };
+    if matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return Err(blocked);
+    }
+    let html = session.text(reply.response).await?;
+    let lowercase = html.to_lowercase();
+    if THROTTLE_MARKERS
+        .iter()
+        .any(|marker| lowercase.contains(marker))
+    {
+        return Err(blocked);
+    }
+    if let Some(link) = from_re(&html, CONTINUE_LINK) {
+        let link = reply.url.join(&link).map_err(|_| Error::NoString)?;
+        if link.host_str() != reply.url.host_str() {
+            return Ok(link.into());
+        }
+        // a confirmation on rlu.ru itself, redirecting to the destination
+        let confirmed = session
+            .send(Method::GET, link.as_str(), Redirects::SameHost)
+            .await?;
+        return Ok(confirmed.url.into());
+    }
+    redirect::from_page(&html).ok_or(Error::ParseFailure { resolver: "rluru" })
+}
@@ -1,26 +1,25 @@
 // ShortURL.AT service
-use super::{custom_redirect_policy, get_client_builder};
-use std::time::Duration;
-
-use futures::future::{ready, TryFutureExt};
+use super::{from_re, Redirects, Session};
+use reqwest::{header::LOCATION, Method};
 
 use crate::{Error, Result};
 
-/// URL Expander for shorturl.at Shortner Service
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    let custom = custom_redirect_policy();
+/// The destination of the interstitial page with a delayed redirect
+static INTERSTITIAL: &str = r#"data-(?:url|href|redirect)\s*=\s*['"](https?://[^'"]+)['"]"#;
 
-    ready(get_client_builder(timeout).redirect(custom).build())
-        .and_then(|client| async move { client.head(url).send().await })
-        .err_into()
-        .and_then(|response| {
-            ready(
-                response
-                    .headers()
-                    .get("location")
-                    .ok_or(Error::NoString)
-                    .and_then(|hv| Ok(hv.to_str()?.into())),
-            )
-        })
-        .await
+/// URL Expander for shorturl.at Shortner Service: the redirect of its
+/// `www.` host, or the data attribute of its interstitial page
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    let reply = session.send(Method::HEAD, url, Redirects::SameHost).await?;
+    if let Some(location) = reply.response.headers.get(LOCATION) {
+        return Ok(location.to_str()?.into());
+    }
+    let host = reply.url.host_str().unwrap_or_default();
+    if host != "shorturl.at" && !host.ends_with(".shorturl.at") {
+        return Ok(reply.url.into());
+    }
+    let html = session.page(reply.url.as_str()).await?;
+    from_re(&html, INTERSTITIAL).ok_or(Error::ParseFailure {
+        resolver: "shorturl",
+    })
 }
@@ -1,35 +1,60 @@
 // SURL.LI Resolver
-use crate::resolvers::{from_url, generic};
-use futures::future::{ready, TryFutureExt};
-use std::time::Duration;
+use crate::resolvers::{decode_entities, from_re, generic, Session};
 
 use crate::{Error, Result};
 
-/// Generic URL Expander
-pub(crate) async fn unshort(url: &str, timeout: Option<Duration>) -> Result<String> {
-    let expanded_url = generic::unshort(url, timeout).await?;
-    Ok(
-        if url.ends_with(expanded_url.split("//").last().unwrap_or_default()) {
-            match get_from_html(url, timeout).await {
-                Ok(u) => u,
-                Err(_) => expanded_url,
+/// Phrases of the JavaScript challenge pages that cannot be solved here, lowercased
+static CHALLENGE_MARKERS: [&str; 4] = [
+    "checking your browser",
+    "enable javascript and cookies",
+    "challenge-form",
+    "cf-chl",
+];
+
+/// The cookie set by the trivial challenge before it reloads the page
+static CHALLENGE_COOKIE: &str = r#"document\.cookie\s*=\s*['"]([^'";=\s]+=[^'";\s]*)"#;
+
+/// Generic URL Expander, answering the cookie challenge of the service once
+pub(crate) async fn unshort(url: &str, session: &Session<'_>) -> Result<String> {
+    let mut answered = false;
+    loop {
+        let expanded_url = generic::unshort(url, session).await?;
+        if !url.ends_with(expanded_url.split("//").last().unwrap_or_default()) {
+            return Ok(expanded_url);
+        }
+        let html = match session.shortener_page() {
+            Some(html) => html,
+            None => session.page(url).await?,
+        };
+        match challenge_cookie(&html) {
+            Some(cookie) if !answered => {
+                session.set_cookie(cookie);
+                answered = true;
             }
-        } else {
-            expanded_url
-        },
-    )
+            Some(_) => return Err(Error::BotChallenge("surl.li".into())),
+            None if is_challenge(&html) => return Err(Error::BotChallenge("surl.li".into())),
+            None => return Ok(from_html(&html).unwrap_or(expanded_url)),
+        }
+    }
+}
+
+/// The `name=value` cookie of a page setting it from JavaScript then
+/// reloading itself
+fn challenge_cookie(html: &str) -> Option<String> {
+    let reloads = html.contains("location.reload") || html.contains("location.href");
+    reloads.then(|| from_re(html, CHALLENGE_COOKIE)).flatten()
+}
+
+/// Whether the page is a JavaScript challenge
+fn is_challenge(html: &str) -> bool {
+    let html = html.to_lowercase();
+    CHALLENGE_MARKERS.iter().any(|marker| html.contains(marker))
 }
 
-async fn get_from_html(url: &str, timeout: Option<Duration>) -> Result<String> {
-    from_url(url, timeout)
-        .and_then(|html| {
-            ready(
-                html.split("api.miniature.io/?url=")
-                    .last()
-                    .and_then(|r| r.split('"').next())
-                    .map(|r| r.to_string())
-                    .ok_or(Error::NoString),
-            )
-        })
-        .await
+/// The destination shown in the preview of the page
+fn from_html(html: &str) -> Option<String> {
+    html.split("api.miniature.io/?url=")
+        .nth(1)
+        .and_then(|r| r.split('"').next())
+        .map(decode_entities)
 }
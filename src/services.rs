@@ -1,6 +1,6 @@
 /// List of domains for some known
 /// URL shortening services.
-pub(crate) static SERVICES: [&str; 91] = [
+pub(crate) static SERVICES: [&str; 92] = [
     "adf.ly",
     "adfoc.us",
     "amzn.to",
@@ -23,6 +23,7 @@ pub(crate) static SERVICES: [&str; 91] = [
     "f.ls",
     "fa.by",
     "fb.me",
+    "feedproxy.google.com",
     "flip.it",
     "fumacrom.com",
     "geni.us",
@@ -96,14 +97,19 @@ pub(crate) static SERVICES: [&str; 91] = [
 
 use url::Url;
 
+/// Lowercase ASCII (punycode) form of a domain, without its trailing dot
+pub(crate) fn ascii_domain(domain: &str) -> String {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_lowercase())
+}
+
 /// Check and tell which URL Shortner Service is used
 pub(crate) fn which_service(url: &str) -> Option<&'static str> {
     let domain = Url::parse(url)
         .or_else(|_| Url::parse(&format!("https://{}", url)))
         .ok()
-        .and_then(|u| u.domain().map(|d| d.to_lowercase()))?;
-
-    let d = domain.strip_suffix('.').unwrap_or(&domain);
+        .and_then(|u| u.domain().map(ascii_domain))?;
+    let d = domain.as_str();
 
     SERVICES
         .iter()
@@ -0,0 +1,141 @@
+// Unlisted domains behaving like shorteners
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+use url::Url;
+
+use crate::{cache::evict_oldest, Expander, HopInfo};
+
+/// Redirects of tiny paths seen before a domain is suggested
+static MIN_REDIRECTS: usize = 3;
+
+/// Longest path (without its `/`) taken for a short code
+static MAX_CODE_LEN: usize = 12;
+
+/// Domains observed at most, the least recently seen being forgotten first
+pub(crate) static MAX_DOMAINS: usize = 10_000;
+
+/// A domain that redirected short codes to other domains, and never did
+/// anything else in the expansions seen, from [`Expander::suggested_services`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedService {
+    pub domain: String,
+    /// Tiny paths of the domain seen redirecting to other domains
+    pub redirects: usize,
+}
+
+/// What the hops on a domain did
+#[derive(Debug, Default)]
+struct Observed {
+    /// Redirects of tiny paths to other domains
+    redirects: usize,
+    /// Anything else: pages, redirects within the domain, long paths
+    other: usize,
+}
+
+/// Observations of the unlisted domains met in expansion chains, with the
+/// time each domain was last seen
+#[derive(Debug, Default)]
+pub(crate) struct Suggestions(Mutex<HashMap<String, (Instant, Observed)>>);
+
+/// Whether the path of a url looks like a short code (`/aB3x`), without a query
+fn is_short_code(url: &Url) -> bool {
+    let code = url.path().trim_start_matches('/');
+    (1..=MAX_CODE_LEN).contains(&code.len())
+        && code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && url.query().is_none()
+}
+
+/// The domain of a url without its `www.`, for the canonical redirects of
+/// websites not to look like they leave the domain
+fn site(url: &Url) -> Option<&str> {
+    url.domain().map(|domain| domain.trim_start_matches("www."))
+}
+
+/// Whether the response to a url, redirecting to `location`, is the one of a
+/// shortener: a short code sent to another domain
+pub(crate) fn redirects_short_code(url: &Url, status: u16, location: Option<&str>) -> bool {
+    let leaves = location
+        .and_then(|location| url.join(location).ok())
+        .is_some_and(|target| site(&target) != site(url));
+    (300..400).contains(&status) && leaves && is_short_code(url)
+}
+
+impl Suggestions {
+    /// Record what the hops of a chain did, skipping the urls already
+    /// `known` as short urls
+    pub(crate) fn record(&self, chain: &[HopInfo], known: impl Fn(&str) -> bool) {
+        let mut observed = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        for hop in chain {
+            let Ok(url) = Url::parse(&hop.url) else {
+                continue;
+            };
+            let Some(domain) = url.domain().filter(|_| !known(&hop.url)) else {
+                continue;
+            };
+            let domain = domain.to_lowercase();
+            if !observed.contains_key(&domain) {
+                evict_oldest(&mut observed, MAX_DOMAINS, |(seen, _)| *seen);
+            }
+            let (seen, entry) = observed
+                .entry(domain)
+                .or_insert_with(|| (Instant::now(), Observed::default()));
+            *seen = Instant::now();
+            if redirects_short_code(&url, hop.status, hop.location.as_deref()) {
+                entry.redirects += 1;
+            } else {
+                entry.other += 1;
+            }
+        }
+    }
+
+    /// The domains that consistently redirected short codes elsewhere, the
+    /// most seen first
+    pub(crate) fn suggested(&self) -> Vec<SuggestedService> {
+        let observed = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut suggested: Vec<_> = observed
+            .iter()
+            .map(|(domain, (_, seen))| (domain, seen))
+            .filter(|(_, seen)| seen.redirects >= MIN_REDIRECTS && seen.other == 0)
+            .map(|(domain, seen)| SuggestedService {
+                domain: domain.clone(),
+                redirects: seen.redirects,
+            })
+            .collect();
+        suggested.sort_by(|a, b| b.redirects.cmp(&a.redirects).then(a.domain.cmp(&b.domain)));
+        suggested
+    }
+}
+
+impl Expander {
+    pub fn suggested_services(&self) -> Vec<SuggestedService> {
+        //! Unlisted domains that redirected short codes (`/aB3x`) to other
+        //! domains at least 3 times, and did nothing else, in the expansions
+        //! made so far with `ExpanderBuilder::suggest_services`; to review
+        //! them before promoting them with `ExpanderBuilder::custom_services`
+        //! ## Example
+        //! ```rust,no_run
+        //! use urlexpand::Expander;
+        //!
+        //! # #[tokio::main]
+        //! # async fn main() -> urlexpand::Result<()> {
+        //! let expander = Expander::builder().suggest_services(true).build()?;
+        //! // ... expand urls
+        //! for suggested in expander.suggested_services() {
+        //!     println!("{} ({} redirects)", suggested.domain, suggested.redirects);
+        //! }
+        //! # Ok(())
+        //! # }
+        //! ```
+        self.inner
+            .suggestions
+            .as_ref()
+            .map(Suggestions::suggested)
+            .unwrap_or_default()
+    }
+}
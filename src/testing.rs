@@ -0,0 +1,392 @@
+// Mock transport and canned shortener responses, to test expansions offline
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use futures::FutureExt;
+use reqwest::header::{HeaderName, HeaderValue, CONTENT_TYPE, LOCATION};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::{
+    recording::{self, encode_body},
+    resolvers::{adfly, Resolver},
+    transport::{
+        BoxFuture, HeaderMap, HttpRequest, HttpResponse, HttpTransport, Middleware, Next,
+        StatusCode,
+    },
+    Error, Result, SERVICES,
+};
+
+/// A canned response of a [`MockTransport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// An empty response with the given status
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    /// A `301 Moved Permanently` to `location`
+    pub fn redirect(location: &str) -> Self {
+        Self::new(301).header(LOCATION.as_str(), location)
+    }
+
+    /// An HTML page with the given status
+    pub fn html(status: u16, body: &str) -> Self {
+        Self::new(status)
+            .header(CONTENT_TYPE.as_str(), "text/html; charset=utf-8")
+            .body(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(self, body: &str) -> Self {
+        self.bytes(body.as_bytes())
+    }
+
+    /// A body that need not be UTF-8, such as a page in another charset
+    pub fn bytes(mut self, body: &[u8]) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    routes: HashMap<String, MockResponse>,
+    requests: Vec<HttpRequest>,
+}
+
+/// A transport answering from canned responses by url, and recording the
+/// requests it gets; unknown urls get a `404 Not Found`.
+///
+/// Clones share their routes and requests, so a clone can be given to the
+/// expander while the test keeps the other.
+///
+/// ## Example
+/// ```rust
+/// use urlexpand::{
+///     testing::{MockResponse, MockTransport},
+///     Expander,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mock = MockTransport::new()
+///     .route("https://bit.ly/abc", MockResponse::redirect("https://example.com/"))
+///     .route("https://example.com/", MockResponse::html(200, "ok"));
+/// let expander = Expander::builder().transport(mock.clone()).build().unwrap();
+/// let expanded = expander.expand("https://bit.ly/abc").await.unwrap();
+/// assert_eq!(expanded.url, "https://example.com/");
+/// assert_eq!(mock.requests().len(), 2);
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A transport answering the short url of every [`fixtures`] entry as
+    /// its shortener would, and every destination with a `200 OK`
+    pub fn with_fixtures() -> Self {
+        fixtures()
+            .iter()
+            .flat_map(fixture_routes)
+            .fold(Self::new(), |mock, (url, response)| {
+                mock.route(&url, response)
+            })
+    }
+
+    /// Answer the requests to `url` (whatever their method) with `response`
+    pub fn route(self, url: &str, response: MockResponse) -> Self {
+        let url = Url::parse(url).map_or_else(|_| url.into(), String::from);
+        self.lock().routes.insert(url, response);
+        self
+    }
+
+    /// Every request received so far
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl HttpTransport for MockTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        let mut url = request.url.clone();
+        url.set_fragment(None);
+        let mut state = self.lock();
+        let response = state
+            .routes
+            .get(url.as_str())
+            .cloned()
+            .unwrap_or_else(|| MockResponse::new(404));
+        state.requests.push(request);
+        drop(state);
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &response.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        let status =
+            StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        async move { Ok(HttpResponse::new(status, headers, response.body)) }.boxed()
+    }
+}
+
+impl From<&Cassette> for MockTransport {
+    /// Replay the interactions of a cassette; when a url was requested more
+    /// than once, its last response is replayed
+    fn from(cassette: &Cassette) -> Self {
+        cassette
+            .interactions
+            .iter()
+            .fold(Self::new(), |mock, interaction| {
+                mock.route(&interaction.url, interaction.response.clone())
+            })
+    }
+}
+
+/// A request made while expanding, and the response it got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    pub response: MockResponse,
+}
+
+/// The HTTP interactions of expansions, saved as JSON to be replayed later
+/// with a [`MockTransport`]: deterministic resolver regression tests, or
+/// reproducible reports of broken shorteners
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Read a cassette saved with [`Cassette::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Write the cassette as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(fs::write(path, self.to_json())?)
+    }
+
+    pub fn to_json(&self) -> String {
+        let interactions: Vec<Value> = self
+            .interactions
+            .iter()
+            .map(|interaction| {
+                let (body, encoding) = encode_body(&interaction.response.body);
+                let mut response = json!({
+                    "status": interaction.response.status,
+                    "headers": interaction.response.headers,
+                    "body": body,
+                });
+                if let Some(encoding) = encoding {
+                    response["encoding"] = encoding.into();
+                }
+                json!({
+                    "request": {"method": interaction.method, "url": interaction.url},
+                    "response": response,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({ "interactions": interactions })).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let invalid = |e: &dyn std::fmt::Display| Error::Cassette(e.to_string());
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(&e))?;
+        let interactions = value["interactions"]
+            .as_array()
+            .ok_or_else(|| invalid(&"no interactions"))?;
+        let interactions = recording::interactions(interactions)?
+            .into_iter()
+            .map(|recorded| Interaction {
+                method: recorded.method,
+                url: recorded.url,
+                response: MockResponse {
+                    status: recorded.status,
+                    headers: recorded.headers,
+                    body: recorded.body,
+                },
+            })
+            .collect();
+        Ok(Self { interactions })
+    }
+}
+
+/// A middleware recording every interaction of the expansions into a
+/// [`Cassette`]; clones share the recording
+///
+/// ## Example
+/// ```rust,no_run
+/// use urlexpand::{testing::Recorder, Expander};
+///
+/// # #[tokio::main]
+/// # async fn main() -> urlexpand::Result<()> {
+/// let recorder = Recorder::new();
+/// let expander = Expander::builder().middleware(recorder.clone()).build()?;
+/// expander.expand("https://bit.ly/3alqLKi").await?;
+/// recorder.cassette().save("bitly.json")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Recorder {
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The interactions recorded so far
+    pub fn cassette(&self) -> Cassette {
+        Cassette {
+            interactions: self
+                .interactions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        }
+    }
+}
+
+impl Middleware for Recorder {
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse>> {
+        let (method, url) = (request.method.to_string(), request.url.to_string());
+        async move {
+            let response = next.run(request).await?;
+            let (status, headers) = (response.status, response.headers.clone());
+            let body = response.bytes().await?;
+            let recorded = MockResponse {
+                status: status.as_u16(),
+                headers: headers
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        Some((name.to_string(), value.to_str().ok()?.into()))
+                    })
+                    .collect(),
+                body: body.to_vec(),
+            };
+            self.interactions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(Interaction {
+                    method,
+                    url,
+                    response: recorded,
+                });
+            Ok(HttpResponse::new(status, headers, body))
+        }
+        .boxed()
+    }
+}
+
+/// A short url of a supported service and the url it expands to with
+/// [`MockTransport::with_fixtures`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub service: &'static str,
+    pub short_url: String,
+    pub expanded_url: String,
+}
+
+/// One fixture for every supported service
+pub fn fixtures() -> Vec<Fixture> {
+    SERVICES
+        .iter()
+        .map(|&service| Fixture {
+            service,
+            short_url: format!("https://{}/urlexpand", service),
+            expanded_url: format!("https://example.com/{}", service),
+        })
+        .collect()
+}
+
+/// The responses of a shortener for a fixture, in the form its resolver
+/// reads, and of its destination
+fn fixture_routes(fixture: &Fixture) -> Vec<(String, MockResponse)> {
+    let destination = fixture.expanded_url.as_str();
+    let mut routes = vec![(destination.into(), MockResponse::html(200, "ok"))];
+    let response = match Resolver::of(fixture.service) {
+        Resolver::Adfly => MockResponse::html(
+            403,
+            &format!(
+                "<script>var ysmm = '{}';</script>",
+                adfly::encode_ysmm(destination)
+            ),
+        ),
+        Resolver::Adfocus => MockResponse::html(
+            403,
+            &format!("<script>var click_url = \"{}\";</script>", destination),
+        ),
+        Resolver::Redirect => MockResponse::html(
+            200,
+            &format!("<script>window.location = \"{}\";</script>", destination),
+        ),
+        Resolver::Rluru => MockResponse::html(
+            200,
+            &format!(
+                "Here is the URL which you want to visit:<br><br>\n<a href=\"{}\">{0}</a>",
+                destination
+            ),
+        ),
+        Resolver::Refresh => MockResponse::html(
+            403,
+            &format!(
+                "<meta http-equiv=\"refresh\" content=\"0; URL={}\">",
+                destination
+            ),
+        ),
+        // the destination is in the location of the first response on another host
+        Resolver::ShortUrl => {
+            let www = fixture.short_url.replacen("://", "://www.", 1);
+            routes.push((www.clone(), MockResponse::redirect(destination)));
+            MockResponse::redirect(&www)
+        }
+        Resolver::HttpRedirect | Resolver::Linkedin | Resolver::Surlli | Resolver::Generic => {
+            MockResponse::redirect(destination)
+        }
+    };
+    routes.push((fixture.short_url.clone(), response));
+    routes
+}
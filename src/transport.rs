@@ -0,0 +1,220 @@
+// Pluggable HTTP backend
+use std::{sync::Arc, time::Duration};
+
+use futures::{
+    stream::{self, BoxStream},
+    FutureExt, StreamExt, TryStreamExt,
+};
+use url::{Host, Url};
+
+pub use bytes::Bytes;
+pub use futures::future::BoxFuture;
+pub use reqwest::{header::HeaderMap, Method, StatusCode};
+
+use crate::{dns, error::TimeoutStage, Error, Result};
+
+/// Body of a response, as it arrives
+pub type BodyStream = BoxStream<'static, Result<Bytes>>;
+
+/// One request made while expanding
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    /// Timeout of the request, when it differs from the transport one
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    /// A request without headers
+    pub fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            timeout: None,
+        }
+    }
+}
+
+/// Response to an [`HttpRequest`]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BodyStream,
+}
+
+impl HttpResponse {
+    /// A response with its whole body at hand
+    pub fn new(status: StatusCode, headers: HeaderMap, body: impl Into<Bytes>) -> Self {
+        let body: Bytes = body.into();
+        Self {
+            status,
+            headers,
+            body: stream::once(async move { Ok(body) }).boxed(),
+        }
+    }
+
+    /// Read the whole body
+    pub async fn bytes(self) -> Result<Bytes> {
+        let chunks: Vec<Bytes> = self.body.try_collect().await?;
+        Ok(match chunks.len() {
+            1 => chunks.into_iter().next().unwrap_or_default(),
+            _ => chunks.concat().into(),
+        })
+    }
+}
+
+/// Sends the requests of the expansions; `reqwest::Client` is the default
+/// implementation, others can plug in any HTTP stack (or a mock)
+///
+/// Implementations must not follow redirects: the expander follows them
+/// itself to record every hop. Timeouts should be reported as
+/// `Error::Timeout`, the expander filling in the elapsed time.
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>>;
+}
+
+impl HttpTransport for reqwest::Client {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        let mut builder = self
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        async move {
+            let response = builder
+                .send()
+                .await
+                .map_err(|e| reqwest_error(e, TimeoutStage::Total))?;
+            Ok(HttpResponse {
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response
+                    .bytes_stream()
+                    .map_err(|e| reqwest_error(e, TimeoutStage::Read))
+                    .boxed(),
+            })
+        }
+        .boxed()
+    }
+}
+
+/// Inspects or modifies every request and response of the expansions:
+/// signing, header injection, logging...
+///
+/// ## Example
+/// ```rust
+/// use urlexpand::{
+///     transport::{BoxFuture, HttpRequest, HttpResponse, Middleware, Next},
+///     Expander, Result,
+/// };
+///
+/// struct ApiKey(&'static str);
+///
+/// impl Middleware for ApiKey {
+///     fn handle<'a>(
+///         &'a self,
+///         mut request: HttpRequest,
+///         next: Next<'a>,
+///     ) -> BoxFuture<'a, Result<HttpResponse>> {
+///         request.headers.insert("x-api-key", self.0.parse().unwrap());
+///         next.run(request)
+///     }
+/// }
+///
+/// let expander = Expander::builder().middleware(ApiKey("secret")).build();
+/// assert!(expander.is_ok());
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Handle a request, usually passing it on with `next.run(request)`
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse>>;
+}
+
+/// The rest of the middleware chain, ending with the transport
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    transport: &'a dyn HttpTransport,
+}
+
+impl<'a> Next<'a> {
+    /// Pass the request to the next middleware, or send it
+    pub fn run(self, request: HttpRequest) -> BoxFuture<'a, Result<HttpResponse>> {
+        match self.middlewares.split_first() {
+            Some((middleware, middlewares)) => middleware.handle(
+                request,
+                Next {
+                    middlewares,
+                    transport: self.transport,
+                },
+            ),
+            None => self.transport.send(request),
+        }
+    }
+}
+
+/// A transport behind a chain of middlewares, run in the order they were added
+pub(crate) struct MiddlewareTransport {
+    pub(crate) middlewares: Vec<Arc<dyn Middleware>>,
+    pub(crate) transport: Arc<dyn HttpTransport>,
+}
+
+impl HttpTransport for MiddlewareTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        Next {
+            middlewares: &self.middlewares,
+            transport: self.transport.as_ref(),
+        }
+        .run(request)
+    }
+}
+
+/// Refuses the requests to private IP addresses and to `localhost`, whose
+/// hosts are never resolved
+pub(crate) struct PublicTransport {
+    pub(crate) transport: Arc<dyn HttpTransport>,
+}
+
+impl HttpTransport for PublicTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse>> {
+        let private = match request.url.host() {
+            Some(Host::Ipv4(ip)) => dns::is_private(ip.into()),
+            Some(Host::Ipv6(ip)) => dns::is_private(ip.into()),
+            Some(Host::Domain(domain)) => {
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                domain == "localhost" || domain.ends_with(".localhost")
+            }
+            None => false,
+        };
+        if private {
+            let host = request.url.host_str().unwrap_or_default().to_string();
+            return async move { Err(Error::PrivateAddress(host)) }.boxed();
+        }
+        self.transport.send(request)
+    }
+}
+
+/// Tell timeouts apart from the other errors of reqwest
+fn reqwest_error(e: reqwest::Error, stage: TimeoutStage) -> Error {
+    if let Some(host) = dns::refused_host(&e) {
+        return Error::PrivateAddress(host);
+    }
+    if !e.is_timeout() {
+        return e.into();
+    }
+    Error::Timeout {
+        elapsed: Duration::ZERO,
+        stage: if e.is_connect() {
+            TimeoutStage::Connect
+        } else {
+            stage
+        },
+    }
+}